@@ -1,4 +1,10 @@
-use clap::Parser;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser};
+
+use crate::atomic::WriteOptions;
+use crate::encoding::PayloadEncoding;
+use crate::output::OutputFormat;
+use crate::png::ChunkPosition;
 
 #[derive(Parser, Debug)]
 #[command(version)]
@@ -7,6 +13,42 @@ pub struct Args {
     pub command: PngMeArgs,
 }
 
+impl Args {
+    /// Checks what clap can't express, so mistakes are usage errors reported before
+    /// any file is read.
+    pub fn validate(&self) -> Result<(), clap::Error> {
+        if let PngMeArgs::Encode(encode) = &self.command {
+            let encrypted =
+                !encode.recipient.is_empty() || encode.password || encode.password_file.is_some();
+            if encode.keyword.is_some() && encrypted && encode.encoding == PayloadEncoding::Plain {
+                return Err(Self::command().error(
+                    ErrorKind::ArgumentConflict,
+                    "Encrypted messages in a text chunk need --encoding base64 or base85",
+                ));
+            }
+        }
+        if let PngMeArgs::SplitEncode(split) = &self.command {
+            let files = split.filepaths.len();
+            if files > u8::MAX as usize {
+                return Err(Self::command().error(
+                    ErrorKind::TooManyValues,
+                    format!("split-encode takes at most {} files, {} were given", u8::MAX, files),
+                ));
+            }
+            if let Some(shares) = split.shares.filter(|&shares| shares as usize != files) {
+                return Err(Self::command().error(
+                    ErrorKind::WrongNumberOfValues,
+                    format!(
+                        "{} shares need {} files, one for each share, but {} were given",
+                        shares, shares, files
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[derive(Parser, Debug)]
 pub enum PngMeArgs {
     /// Encode a message in a PNG file
@@ -17,57 +59,604 @@ pub enum PngMeArgs {
     Remove(RemoveArgs),
     /// Print a PNG file
     Print(PrintArgs),
+    /// Print a hex dump of a chunk's data
+    Dump(DumpArgs),
+    /// Write a chunk's data to a file
+    Extract(ExtractArgs),
+    /// Insert a chunk read from a file into a PNG file
+    Inject(InjectArgs),
+    /// Remove metadata chunks from a PNG file
+    Strip(StripArgs),
+    /// Reorder and clean up the chunks of a PNG file
+    Normalize(NormalizeArgs),
+    /// Fix bad CRCs, trailing garbage and a missing IEND in a damaged PNG file
+    Repair(RepairArgs),
+    /// Extract every PNG embedded in or concatenated to a file
+    Carve(CarveArgs),
+    /// Show, extract, append or remove data after the IEND chunk
+    Trailer(TrailerArgs),
+    /// Compare the chunks and image data of two PNG files
+    Diff(DiffArgs),
+    /// List the labelled and unlabelled messages stored in a PNG file, including
+    /// plain text messages written by older versions of pngme
+    ListMessages(ListMessagesArgs),
+    /// Generate a key pair for encrypting messages to a recipient
+    Keygen(KeygenArgs),
+    /// Split a message into shares and hide one share in each of several PNG files
+    SplitEncode(SplitEncodeArgs),
+    /// Recover a message from the shares hidden in PNG files by split-encode
+    CombineDecode(CombineDecodeArgs),
 }
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct EncodeArgs {
-    /// File path
-    #[arg(short, long)]
-    pub filepath: String,
+    /// File paths, globs, directories (with --recursive) or - for stdin
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub filepath: Vec<String>,
+
+    /// Process every PNG file inside directories given in --filepath
+    #[arg(long)]
+    pub recursive: bool,
 
     /// Chunk type
-    #[arg(short, long)]
-    pub chunk_type: String,
+    #[arg(short, long, required_unless_present_any = ["type_passphrase", "random_type", "mimic"])]
+    pub chunk_type: Option<String>,
+
+    /// Store the message in a private chunk type derived from this passphrase. The
+    /// payload is encrypted with it too, so nothing in the chunk shows it is from pngme
+    #[arg(long, conflicts_with_all = ["chunk_type", "keyword"])]
+    pub type_passphrase: Option<String>,
+
+    /// Store the message in a random private chunk type. Only the type is disguised,
+    /// the payload header still shows the chunk is from pngme
+    #[arg(long, conflicts_with_all = ["chunk_type", "type_passphrase", "keyword"])]
+    pub random_type: bool,
+
+    /// Store the message in a private chunk type written by real software, e.g. mkBF.
+    /// Only the type is disguised, the payload header still shows the chunk is from pngme
+    #[arg(long, conflicts_with_all = ["chunk_type", "type_passphrase", "random_type", "keyword"])]
+    pub mimic: bool,
 
     /// Message
     //#[arg(short, long, default_value_t = String::from("Hello"))]
     #[arg(short, long)]
     pub message: String,
 
-    /// Output file (optional)
+    /// Output file when encoding a single file, or - for stdout. Without this,
+    /// --out-dir or --name-template the input file is rewritten in place
+    #[arg(short, long, conflicts_with_all = ["out_dir", "name_template"])]
+    pub out_file: Option<String>,
+
+    /// Directory to write the results to, named by --name-template
+    #[arg(long)]
+    pub out_dir: Option<String>,
+
+    /// Name of each result using {name}, {stem} and {ext} of the input [default: {stem}.encoded.png]
+    #[arg(long)]
+    pub name_template: Option<String>,
+
+    /// Store the message in a tEXt, zTXt or iTXt chunk under this keyword
     #[arg(short, long)]
-    pub out_file: String,
+    pub keyword: Option<String>,
+
+    /// Label the message, e.g. with its recipient, so it can be told apart from others.
+    /// Labels are stored in plain text, even when the message is encrypted
+    #[arg(short, long, conflicts_with = "keyword")]
+    pub label: Option<String>,
+
+    /// Encrypt the message to this public key, or to the keys listed in this file.
+    /// Can be given several times
+    #[arg(short, long)]
+    pub recipient: Vec<String>,
+
+    /// Encrypt the message with a password, read from PNGME_PASSWORD or asked for.
+    /// The label and the fact that the chunk is an encrypted payload stay visible
+    #[arg(short, long, group = "password_source", conflicts_with = "recipient")]
+    pub password: bool,
+
+    /// Encrypt the message with the password on the first line of this file
+    #[arg(long, value_name = "FILE", group = "password_source", conflicts_with = "recipient")]
+    pub password_file: Option<String>,
+
+    /// A second message stored alongside the first, revealed only by its own password.
+    /// Nothing in the chunk shows whether a second message is there
+    #[arg(long, requires_all = ["password_source", "decoy_password_source"])]
+    pub decoy_message: Option<String>,
+
+    /// Read the password of --decoy-message, which must differ from the first, from
+    /// PNGME_DECOY_PASSWORD or ask for it
+    #[arg(long, group = "decoy_password_source", requires = "decoy_message")]
+    pub decoy_password: bool,
+
+    /// Read the password of --decoy-message from the first line of this file
+    #[arg(long, value_name = "FILE", group = "decoy_password_source", requires = "decoy_message")]
+    pub decoy_password_file: Option<String>,
+
+    /// Encoding applied to the message before it is stored
+    #[arg(short, long, value_enum, default_value_t)]
+    pub encoding: PayloadEncoding,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
 }
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct DecodeArgs {
+    /// File paths, globs, directories (with --recursive) or - for stdin
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub filepath: Vec<String>,
+
+    /// Process every PNG file inside directories given in --filepath
+    #[arg(long)]
+    pub recursive: bool,
+
+    /// Chunk type
+    #[arg(short, long, required_unless_present_any = ["auto", "type_passphrase"])]
+    pub chunk_type: Option<String>,
+
+    /// Look for the message in the chunk type derived from this passphrase and decrypt
+    /// the payload with it
+    #[arg(long, conflicts_with_all = ["chunk_type", "keyword"])]
+    pub type_passphrase: Option<String>,
+
+    /// Look for the message in a text chunk with this keyword
+    #[arg(short, long)]
+    pub keyword: Option<String>,
+
+    /// Only decode messages with this label
+    #[arg(short, long, conflicts_with = "keyword")]
+    pub label: Option<String>,
+
+    /// Decrypt messages with the secret keys in this identity file. Can be given
+    /// several times
+    #[arg(long, value_name = "FILE")]
+    pub identity: Vec<String>,
+
+    /// Decrypt messages with a password, read from PNGME_PASSWORD or asked for
+    #[arg(short, long, conflicts_with = "password_file")]
+    pub password: bool,
+
+    /// Decrypt messages with the password on the first line of this file
+    #[arg(long, value_name = "FILE")]
+    pub password_file: Option<String>,
+
+    /// Decode every chunk of the type with its index and offset, not just the first
+    #[arg(short, long, conflicts_with = "index")]
+    pub all: bool,
+
+    /// Decode the chunk of the type at this index, counting every chunk of the type
+    /// from 0. The chunk must also match --keyword and --label if given
+    #[arg(short, long)]
+    pub index: Option<usize>,
+
+    /// Look for messages in every private or unknown ancillary chunk
+    #[arg(long, conflicts_with_all = ["chunk_type", "type_passphrase", "keyword", "label", "all", "index"])]
+    pub auto: bool,
+
+    /// Encoding the message was stored with
+    #[arg(short, long, value_enum, default_value_t)]
+    pub encoding: PayloadEncoding,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct RemoveArgs {
     /// File path
     #[arg(short, long)]
     pub filepath: String,
 
+    /// Chunk type
+    #[arg(
+        short,
+        long,
+        required_unless_present_any = ["index", "ancillary", "private", "unknown", "label"]
+    )]
+    pub chunk_type: Option<String>,
+
+    /// Remove every matching chunk instead of only the first
+    #[arg(short, long, conflicts_with = "index")]
+    pub all: bool,
+
+    /// Remove the chunk at this index, counted among chunks of --chunk-type if given
+    #[arg(short, long)]
+    pub index: Option<usize>,
+
+    /// Only remove ancillary chunks
+    #[arg(long)]
+    pub ancillary: bool,
+
+    /// Only remove private chunks
+    #[arg(long)]
+    pub private: bool,
+
+    /// Only remove chunks that are not defined by the PNG specification
+    #[arg(long)]
+    pub unknown: bool,
+
+    /// Only remove messages with this label
+    #[arg(short, long)]
+    pub label: Option<String>,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct PrintArgs {
+    /// File paths, globs, directories (with --recursive) or - for stdin
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub filepath: Vec<String>,
+
+    /// Process every PNG file inside directories given in --filepath
+    #[arg(long)]
+    pub recursive: bool,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct DumpArgs {
+    /// File path, or - for stdin
+    #[arg(short, long)]
+    pub filepath: String,
+
     /// Chunk type
     #[arg(short, long)]
     pub chunk_type: String,
+
+    /// Which chunk of that type to use, starting at 0
+    #[arg(short, long, default_value_t = 0)]
+    pub index: usize,
 }
 
 #[derive(Parser, Debug)]
 #[command(version)]
-pub struct RemoveArgs {
-    /// File path
+pub struct ExtractArgs {
+    /// File path, or - for stdin
     #[arg(short, long)]
     pub filepath: String,
 
     /// Chunk type
     #[arg(short, long)]
     pub chunk_type: String,
+
+    /// Which chunk of that type to use, starting at 0
+    #[arg(short, long, default_value_t = 0)]
+    pub index: usize,
+
+    /// Output file
+    #[arg(short, long)]
+    pub out: String,
+
+    /// Write the whole chunk including its length, type and CRC
+    #[arg(short, long)]
+    pub raw: bool,
+
+    /// Print the file that would be written and its size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
 }
 
 #[derive(Parser, Debug)]
 #[command(version)]
-pub struct PrintArgs {
+pub struct InjectArgs {
+    /// File path, or - for stdin
+    #[arg(short, long)]
+    pub filepath: String,
+
+    /// Chunk type
+    #[arg(short, long, required_unless_present = "raw_chunk")]
+    pub chunk_type: Option<String>,
+
+    /// File holding the chunk's data
+    #[arg(short, long, required_unless_present = "raw_chunk")]
+    pub data_file: Option<String>,
+
+    /// File holding a complete chunk (length, type, data and CRC) to insert as-is
+    #[arg(short, long, conflicts_with_all = ["chunk_type", "data_file"])]
+    pub raw_chunk: Option<String>,
+
+    /// Where to insert the chunk: before-iend, after-ihdr or index:N
+    #[arg(short, long, default_value = "before-iend")]
+    pub position: ChunkPosition,
+
+    /// Output file, or - for stdout
+    #[arg(short, long)]
+    pub out_file: String,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct StripArgs {
+    /// File paths, globs or directories (with --recursive)
+    #[arg(short, long, num_args = 1.., required = true)]
+    pub filepath: Vec<String>,
+
+    /// Process every PNG file inside directories given in --filepath
+    #[arg(long)]
+    pub recursive: bool,
+
+    /// Remove every ancillary chunk
+    #[arg(short, long, group = "preset")]
+    pub all: bool,
+
+    /// Only remove tIME, tEXt, zTXt, iTXt and eXIf chunks
+    #[arg(short, long, group = "preset")]
+    pub privacy: bool,
+
+    /// Remove every ancillary chunk except tRNS, gAMA, cHRM, sRGB, iCCP and cICP (default)
+    #[arg(long, group = "preset")]
+    pub keep_color: bool,
+
+    /// Also remove unknown chunks that are not safe to copy, which depend on the image
+    /// data and are dropped by other editors anyway
+    #[arg(short, long)]
+    pub unsafe_to_copy: bool,
+
+    /// Also keep chunks of this type, can be repeated
+    #[arg(short, long)]
+    pub keep: Vec<String>,
+
+    /// Output file when stripping a single file, defaults to overwriting the input file
+    #[arg(short, long)]
+    pub out_file: Option<String>,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct NormalizeArgs {
     /// File path
     #[arg(short, long)]
     pub filepath: String,
+
+    /// Merge the IDAT chunks and resplit them into chunks of at most this many bytes
+    #[arg(short, long)]
+    pub idat_size: Option<usize>,
+
+    /// Keep unknown unsafe-to-copy chunks even when critical chunks changed
+    #[arg(short, long)]
+    pub keep_unsafe: bool,
+
+    /// Output file, defaults to overwriting the input file
+    #[arg(short, long)]
+    pub out_file: Option<String>,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct RepairArgs {
+    /// File path
+    #[arg(short, long)]
+    pub filepath: String,
+
+    /// Only recompute the CRCs of ancillary chunks
+    #[arg(short, long)]
+    pub ancillary_only: bool,
+
+    /// Output file, defaults to overwriting the input file
+    #[arg(short, long)]
+    pub out_file: Option<String>,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct CarveArgs {
+    /// File path, or - for stdin
+    #[arg(short, long)]
+    pub filepath: String,
+
+    /// Directory to write the recovered PNG files to
+    #[arg(short, long, default_value = ".")]
+    pub out_dir: String,
+
+    /// Print the files that would be written and their sizes without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct TrailerArgs {
+    /// File path
+    #[arg(short, long)]
+    pub filepath: String,
+
+    /// Write the trailing data to this file
+    #[arg(short, long, group = "action")]
+    pub extract: Option<String>,
+
+    /// Append the contents of this file to the trailing data
+    #[arg(short, long, group = "action")]
+    pub append: Option<String>,
+
+    /// Remove the trailing data
+    #[arg(short, long, group = "action")]
+    pub remove: bool,
+
+    /// Output file for --append and --remove, defaults to overwriting the input file
+    #[arg(short, long)]
+    pub out_file: Option<String>,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct DiffArgs {
+    /// Original file
+    pub a: String,
+
+    /// Changed file
+    pub b: String,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct ListMessagesArgs {
+    /// File path, or - for stdin
+    #[arg(short, long)]
+    pub filepath: String,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct KeygenArgs {
+    /// File to save the secret key to, prints it if not given
+    #[arg(short, long)]
+    pub out_file: Option<String>,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct SplitEncodeArgs {
+    /// Files to hide the shares in, one share each. Without --out-dir or
+    /// --name-template each file is rewritten in place
+    #[arg(required = true)]
+    pub filepaths: Vec<String>,
+
+    /// Number of shares, which must match the number of files
+    #[arg(short = 'n', long)]
+    pub shares: Option<u8>,
+
+    /// Number of shares needed to recover the message
+    #[arg(short, long)]
+    pub threshold: u8,
+
+    /// Chunk type
+    #[arg(short, long)]
+    pub chunk_type: String,
+
+    /// Message
+    #[arg(short, long)]
+    pub message: String,
+
+    /// Label the shares so they can be told apart from those of other messages. Labels
+    /// are stored in plain text
+    #[arg(short, long)]
+    pub label: Option<String>,
+
+    /// Directory to write the results to, named by --name-template
+    #[arg(long)]
+    pub out_dir: Option<String>,
+
+    /// Name of each result using {name}, {stem} and {ext} of the input, {stem}.encoded.png
+    /// if only --out-dir is given
+    #[arg(long)]
+    pub name_template: Option<String>,
+
+    /// Print the chunks that would change and the resulting size without writing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    #[command(flatten)]
+    pub write: WriteOptions,
+}
+
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct CombineDecodeArgs {
+    /// Files holding the shares, or - for stdin
+    #[arg(required = true)]
+    pub filepaths: Vec<String>,
+
+    /// Only look for shares in chunks of this type
+    #[arg(short, long)]
+    pub chunk_type: Option<String>,
+
+    /// Only use shares with this label
+    #[arg(short, long)]
+    pub label: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(args: &[&str]) -> Result<(), clap::Error> {
+        Args::try_parse_from(args)?.validate()
+    }
+
+    #[test]
+    fn test_validate_split_encode_shares() {
+        let split = [
+            "pngme", "split-encode", "a.png", "b.png", "-t", "2", "-c", "ruSt", "-m", "hi",
+        ];
+        assert!(validate(&split).is_ok());
+        assert!(validate(&[&split[..], &["-n", "2"]].concat()).is_ok());
+
+        let error = validate(&[&split[..], &["-n", "3"]].concat()).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::WrongNumberOfValues);
+    }
+
+    #[test]
+    fn test_validate_encrypted_text_chunk_encoding() {
+        let encode = ["pngme", "encode", "-f", "a.png", "-c", "tEXt", "-m", "hi", "-k", "Comment"];
+        assert!(validate(&[&encode[..], &["-p"]].concat()).is_err());
+        assert!(validate(&[&encode[..], &["-p", "-e", "base64"]].concat()).is_ok());
+        assert!(validate(&encode).is_ok());
+    }
 }
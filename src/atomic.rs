@@ -0,0 +1,276 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How a command may replace files on disk. Shared by every command that writes files.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Overwrite an output file that already exists
+    #[arg(long)]
+    pub force: bool,
+
+    /// Copy a file to <file><SUFFIX> before replacing it (defaults to .bak)
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak"
+    )]
+    pub backup: Option<String>,
+
+    /// Keep the modification time of a file that is replaced
+    #[arg(long)]
+    pub keep_mtime: bool,
+
+    /// Unix permissions to create the file and any backup with, instead of keeping
+    /// those of the file that is replaced. Set by commands, not on the command line
+    #[arg(skip)]
+    pub mode: Option<u32>,
+}
+
+/// Writes `bytes` to `path` without ever leaving a half written file behind.
+///
+/// The data goes to a temporary file in the same directory, which is synced and
+/// then renamed over `path`. An existing file keeps its permissions unless `mode`
+/// is set, and its modification time too with `keep_mtime`. Unless `in_place` says the command is
+/// meant to rewrite its input, an existing file is only replaced with `force`.
+pub fn write_file(
+    path: &Path,
+    bytes: &[u8],
+    in_place: bool,
+    options: &WriteOptions,
+) -> crate::Result<()> {
+    let existing = match fs::metadata(path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some(metadata) = &existing {
+        if metadata.is_dir() {
+            return Err(format!("'{}' is a directory", path.display()).into());
+        }
+        if !in_place && !options.force {
+            return Err(format!(
+                "'{}' already exists, use --force to overwrite it",
+                path.display()
+            )
+            .into());
+        }
+        if let Some(suffix) = &options.backup {
+            backup(path, &with_suffix(path, suffix), options.mode)?;
+        }
+    }
+
+    // Unique per write, as a batch writes several files from one process at once
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let write = WRITES.fetch_add(1, Ordering::Relaxed);
+    let temp_path = with_suffix(path, &format!(".pngme-{}-{}.tmp", std::process::id(), write));
+    let result = write_temp(&temp_path, bytes, existing.as_ref(), options)
+        .and_then(|_| Ok(fs::rename(&temp_path, path)?));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+
+    sync_parent(path);
+    Ok(())
+}
+
+fn write_temp(
+    temp_path: &Path,
+    bytes: &[u8],
+    existing: Option<&fs::Metadata>,
+    options: &WriteOptions,
+) -> crate::Result<()> {
+    let mut file = create(temp_path, options.mode)?;
+    file.write_all(bytes)?;
+
+    if let Some(metadata) = existing {
+        if options.mode.is_none() {
+            file.set_permissions(metadata.permissions())?;
+        }
+        if options.keep_mtime {
+            file.set_modified(metadata.modified()?)?;
+        }
+    }
+
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Creates `path`, with `mode` from the start where the platform has Unix permissions.
+fn create(path: &Path, mode: Option<u32>) -> crate::Result<File> {
+    let mut file_options = File::options();
+    file_options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        file_options.mode(mode);
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    let file = file_options.open(path)?;
+    // The mode only applies to new files, so an old backup is tightened too
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    Ok(file)
+}
+
+/// Copies `path` to `backup_path`. Without `mode` the copy keeps the permissions
+/// of `path`.
+fn backup(path: &Path, backup_path: &Path, mode: Option<u32>) -> crate::Result<()> {
+    if mode.is_none() {
+        fs::copy(path, backup_path)?;
+        return Ok(());
+    }
+    let mut backup = create(backup_path, mode)?;
+    std::io::copy(&mut File::open(path)?, &mut backup)?;
+    backup.sync_all()?;
+    Ok(())
+}
+
+/// Appends `suffix` to the file name of `path`, e.g. `image.png` to `image.png.bak`.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Makes the rename durable on platforms where a directory can be synced. This is
+/// best effort, the data itself was already synced.
+fn sync_parent(path: &Path) {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(parent) {
+        let _ = dir.sync_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pngme-atomic-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_new_file() {
+        let dir = temp_dir("new");
+        let path = dir.join("out.png");
+        write_file(&path, b"new", false, &WriteOptions::default()).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_refuse_overwrite_without_force() {
+        let dir = temp_dir("force");
+        let path = dir.join("out.png");
+        fs::write(&path, b"old").unwrap();
+
+        assert!(write_file(&path, b"new", false, &WriteOptions::default()).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+
+        let options = WriteOptions {
+            force: true,
+            ..Default::default()
+        };
+        write_file(&path, b"new", false, &options).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_in_place_with_backup() {
+        let dir = temp_dir("backup");
+        let path = dir.join("in.png");
+        fs::write(&path, b"old").unwrap();
+
+        let options = WriteOptions {
+            backup: Some(String::from(".orig")),
+            ..Default::default()
+        };
+        write_file(&path, b"new", true, &options).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read(dir.join("in.png.orig")).unwrap(), b"old");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_keep_mtime() {
+        let dir = temp_dir("mtime");
+        let path = dir.join("in.png");
+        fs::write(&path, b"old").unwrap();
+        let mtime = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let options = WriteOptions {
+            keep_mtime: true,
+            ..Default::default()
+        };
+        write_file(&path, b"new", true, &options).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), mtime);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("permissions");
+        let path = dir.join("in.png");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        write_file(&path, b"new", true, &WriteOptions::default()).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("mode");
+        let path = dir.join("secret.key");
+        fs::write(&path, b"old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let options = WriteOptions {
+            backup: Some(String::from(".bak")),
+            mode: Some(0o600),
+            ..Default::default()
+        };
+        write_file(&path, b"new", true, &options).unwrap();
+        for path in [path.clone(), dir.join("secret.key.bak")] {
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert_eq!(fs::read(dir.join("secret.key.bak")).unwrap(), b"old");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
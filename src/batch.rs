@@ -0,0 +1,264 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::output::{self, OutputFormat};
+
+/// Expands each of `patterns` into PNG file paths. A pattern can be a file, a
+/// glob such as `images/*.png`, or with `recursive` set a directory whose PNG
+/// files are all included.
+pub fn expand_paths(patterns: &[String], recursive: bool) -> crate::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+
+    for pattern in patterns {
+        let path = Path::new(pattern);
+        if crate::stdio::is_stdio(path) {
+            paths.push(path.to_path_buf());
+        } else if path.is_dir() {
+            if !recursive {
+                return Err(format!(
+                    "'{}' is a directory, use --recursive to process it",
+                    pattern
+                )
+                .into());
+            }
+            collect_png_files(path, &mut paths)?;
+        } else if pattern.contains(['*', '?', '[']) {
+            let before = paths.len();
+            for entry in glob::glob(pattern)? {
+                let entry = entry?;
+                if entry.is_dir() && recursive {
+                    collect_png_files(&entry, &mut paths)?;
+                } else if entry.is_file() {
+                    paths.push(entry);
+                }
+            }
+            if paths.len() == before {
+                return Err(format!("No files match '{}'", pattern).into());
+            }
+        } else {
+            paths.push(path.to_path_buf());
+        }
+    }
+
+    Ok(paths)
+}
+
+fn collect_png_files(dir: &Path, paths: &mut Vec<PathBuf>) -> crate::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            collect_png_files(&entry, paths)?;
+        } else if entry
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("png"))
+        {
+            paths.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// The naming template used when a command writes next to or away from its input
+/// instead of overwriting it.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{stem}.encoded.png";
+
+/// Returns an output path that more than one input maps to, if any, e.g. for inputs
+/// with the same file name in different directories written to one `--out-dir`.
+pub fn colliding_output(outputs: &[PathBuf]) -> Option<&PathBuf> {
+    let mut seen = HashSet::new();
+    outputs.iter().find(|output| !seen.insert(*output))
+}
+
+/// Builds the output path for `input` from `template`, placed in `out_dir` if one
+/// is given and next to `input` otherwise. The template can use `{name}` (the
+/// input's file name), `{stem}` (its name without extension) and `{ext}`.
+pub fn output_path(input: &Path, template: &str, out_dir: Option<&Path>) -> crate::Result<PathBuf> {
+    let part = |part: Option<&std::ffi::OsStr>| {
+        part.map(|part| part.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+    let name = part(input.file_name());
+    let stem = part(input.file_stem());
+    let ext = part(input.extension());
+
+    let mut file_name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        file_name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unclosed placeholder in template '{}'", template))?;
+        match &rest[start + 1..start + end] {
+            "name" => file_name.push_str(&name),
+            "stem" => file_name.push_str(&stem),
+            "ext" => file_name.push_str(&ext),
+            other => {
+                return Err(format!(
+                    "Unknown placeholder '{{{}}}' in template '{}', expected {{name}}, {{stem}} or {{ext}}",
+                    other, template
+                )
+                .into())
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    file_name.push_str(rest);
+
+    if file_name.is_empty() || file_name.contains(['/', '\\']) {
+        return Err(format!("Template '{}' does not give a file name", template).into());
+    }
+
+    let dir = match out_dir {
+        Some(dir) => dir,
+        None => input.parent().unwrap_or(Path::new("")),
+    };
+    Ok(dir.join(file_name))
+}
+
+/// Runs `process` on every path in parallel and prints each file's output in the
+/// original order.
+///
+/// A single file behaves exactly like the command always has. With several files
+/// text output gets a `==> path <==` header before each file, JSON and YAML output
+/// is combined with `output::render_batch`, a summary is written to stderr and an
+/// error is returned if any file failed.
+pub fn run<F>(paths: &[PathBuf], format: OutputFormat, process: F) -> crate::Result<()>
+where
+    F: Fn(&Path) -> crate::Result<String> + Sync,
+{
+    if let [path] = paths {
+        print!("{}", process(path)?);
+        return Ok(());
+    }
+
+    let results: Vec<(String, Result<String, String>)> = paths
+        .par_iter()
+        .map(|path| {
+            let result = process(path).map_err(|e| e.to_string());
+            (path.display().to_string(), result)
+        })
+        .collect();
+
+    for (index, (path, result)) in results.iter().enumerate() {
+        if format == OutputFormat::Text {
+            if index > 0 {
+                println!();
+            }
+            println!("==> {} <==", path);
+            if let Ok(output) = result {
+                print!("{}", output);
+            }
+        }
+        if let Err(e) = result {
+            eprintln!("Error: {}: {}", path, e);
+        }
+    }
+    if format != OutputFormat::Text {
+        print!("{}", output::render_batch(&results, format)?);
+    }
+
+    let failed = results.iter().filter(|(_, result)| result.is_err()).count();
+    eprintln!(
+        "{} file(s) processed, {} succeeded, {} failed",
+        paths.len(),
+        paths.len() - failed,
+        failed
+    );
+    if failed > 0 {
+        return Err(format!("{} of {} file(s) failed", failed, paths.len()).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pngme-batch-{}-{}", name, std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("a.png"), b"").unwrap();
+        fs::write(dir.join("notes.txt"), b"").unwrap();
+        fs::write(dir.join("nested").join("b.PNG"), b"").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_expand_recursive() {
+        let dir = testing_dir("recursive");
+        let pattern = dir.to_string_lossy().to_string();
+
+        assert!(expand_paths(std::slice::from_ref(&pattern), false).is_err());
+
+        let paths = expand_paths(&[pattern], true).unwrap();
+        assert_eq!(
+            paths,
+            vec![dir.join("a.png"), dir.join("nested").join("b.PNG")]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let dir = testing_dir("glob");
+        let pattern = dir.join("*.png").to_string_lossy().to_string();
+
+        let paths = expand_paths(&[pattern], false).unwrap();
+        assert_eq!(paths, vec![dir.join("a.png")]);
+
+        let missing = dir.join("*.gif").to_string_lossy().to_string();
+        assert!(expand_paths(&[missing], false).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_run_reports_failures() {
+        let paths = vec![PathBuf::from("ok"), PathBuf::from("fail")];
+        let result = run(&paths, OutputFormat::Text, |path| {
+            if path == Path::new("fail") {
+                Err("failed".into())
+            } else {
+                Ok(String::new())
+            }
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_output_path() {
+        let input = Path::new("images/cat.png");
+        assert_eq!(
+            output_path(input, DEFAULT_NAME_TEMPLATE, None).unwrap(),
+            Path::new("images/cat.encoded.png")
+        );
+        assert_eq!(
+            output_path(input, "{name}", Some(Path::new("out"))).unwrap(),
+            Path::new("out/cat.png")
+        );
+        assert_eq!(
+            output_path(input, "{stem}-secret.{ext}", None).unwrap(),
+            Path::new("images/cat-secret.png")
+        );
+        assert!(output_path(input, "{size}.png", None).is_err());
+        assert!(output_path(input, "{stem", None).is_err());
+        assert!(output_path(input, "sub/{name}", None).is_err());
+    }
+
+    #[test]
+    fn test_colliding_output() {
+        let out = Some(Path::new("out"));
+        let outputs: Vec<PathBuf> = ["a/cat.png", "b/dog.png", "c/cat.png"]
+            .iter()
+            .map(|input| output_path(Path::new(input), DEFAULT_NAME_TEMPLATE, out).unwrap())
+            .collect();
+        assert_eq!(colliding_output(&outputs), Some(&PathBuf::from("out/cat.encoded.png")));
+        assert_eq!(colliding_output(&outputs[..2]), None);
+    }
+}
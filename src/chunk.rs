@@ -1,3 +1,5 @@
+#![allow(clippy::legacy_numeric_constants)]
+
 use std::{
     fmt::{self, Display},
     str::from_utf8,
@@ -7,6 +9,7 @@ use std::{
 use crc::{Crc, CRC_32_ISO_HDLC};
 
 use crate::chunk_type::ChunkType;
+use crate::known_chunk::{ColorType, KnownChunk};
 
 pub struct Chunk {
     length: u32,
@@ -32,7 +35,7 @@ impl Chunk {
         }
     }
 
-    fn length(&self) -> u32 {
+    pub fn length(&self) -> u32 {
         self.length
     }
 
@@ -40,20 +43,70 @@ impl Chunk {
         &self.chunk_type
     }
 
+    #[allow(clippy::needless_return)]
     pub fn chunk_data(&self) -> &[u8] {
         let needed_slice = &self.chunk_data;
         return needed_slice;
     }
 
-    fn crc(&self) -> u32 {
+    pub fn crc(&self) -> u32 {
         self.crc
     }
 
+    /// Computes the CRC of this chunk's type and data.
+    pub fn calculated_crc(&self) -> u32 {
+        let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+        let mut digest = crc.digest();
+        digest.update(&self.chunk_type.chunk_type);
+        digest.update(&self.chunk_data);
+        digest.finalize()
+    }
+
+    /// Returns true if the stored CRC matches the chunk's type and data.
+    pub fn has_valid_crc(&self) -> bool {
+        self.crc == self.calculated_crc()
+    }
+
+    /// Replaces the stored CRC with the correct one. Returns true if it changed.
+    pub fn fix_crc(&mut self) -> bool {
+        let calculated_crc = self.calculated_crc();
+        let changed = self.crc != calculated_crc;
+        self.crc = calculated_crc;
+        changed
+    }
+
+    /// Parses a chunk like `TryFrom<&[u8]>` does but keeps the stored CRC even if
+    /// it doesn't match, so damaged files can be inspected and repaired.
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<Chunk, Box<dyn std::error::Error>> {
+        if bytes.len() < 12 {
+            return Err("Data has length less than 12".into());
+        }
+
+        let data_bytes = &bytes[8..bytes.len() - 4];
+        Ok(Self {
+            length: data_bytes.len() as u32,
+            chunk_type: ChunkType {
+                chunk_type: bytes[4..8].to_vec(),
+            },
+            chunk_data: data_bytes.to_vec(),
+            crc: u32::from_be_bytes(bytes[bytes.len() - 4..].try_into()?),
+        })
+    }
+
     pub fn data_as_string(&self) -> Result<String, std::str::Utf8Error> {
         let result = from_utf8(&self.chunk_data)?;
         Ok(result.to_string())
     }
 
+    /// Decodes this chunk into a `KnownChunk`, or returns `None` if its type isn't
+    /// a standard chunk pngme understands. See `KnownChunk::from_chunk`.
+    pub fn known(
+        &self,
+        color_type: Option<ColorType>,
+    ) -> Result<Option<KnownChunk>, Box<dyn std::error::Error>> {
+        KnownChunk::from_chunk(self, color_type)
+    }
+
     pub fn as_bytes(&self) -> Vec<u8> {
         let length: Vec<u8> = self.length.to_be_bytes().to_vec();
         let chunk_type_bytes = self.chunk_type.chunk_type.clone();
@@ -72,6 +125,7 @@ impl Chunk {
 impl TryFrom<&[u8]> for Chunk {
     type Error = Box<dyn std::error::Error>;
 
+    #[allow(clippy::needless_return)]
     fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
         if bytes.len() < 12 {
             return Err("try_from failed. Data has length less than 12".into());
@@ -118,6 +172,26 @@ impl Display for Chunk {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Chunk {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let data: String = self
+            .chunk_data
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        let mut state = serializer.serialize_struct("Chunk", 4)?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("chunk_type", &self.chunk_type)?;
+        state.serialize_field("data", &data)?;
+        state.serialize_field("crc", &self.crc)?;
+        state.end()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -227,6 +301,22 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_lenient_chunk_from_bytes() {
+        let mut bytes = testing_chunk().as_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+
+        assert!(Chunk::try_from(bytes.as_ref()).is_err());
+
+        let mut chunk = Chunk::from_bytes_lenient(bytes.as_ref()).unwrap();
+        assert!(!chunk.has_valid_crc());
+        assert_eq!(chunk.calculated_crc(), 2882656334);
+        assert!(chunk.fix_crc());
+        assert!(chunk.has_valid_crc());
+        assert!(!chunk.fix_crc());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -247,4 +337,14 @@ mod tests {
 
         let _chunk_string = format!("{}", chunk);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_chunk_serialize() {
+        let chunk = Chunk::new(ChunkType::from_str("RuSt").unwrap(), b"Hi".to_vec());
+        let json = serde_json::to_value(&chunk).unwrap();
+        assert_eq!(json["chunk_type"], "RuSt");
+        assert_eq!(json["length"], 2);
+        assert_eq!(json["data"], "4869");
+    }
 }
@@ -2,14 +2,24 @@ use std::error::Error;
 use std::fmt::Display;
 use std::str::FromStr;
 
+use sha2::{Digest, Sha256};
+
+use crate::crypto;
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct ChunkType {
     pub chunk_type: Vec<u8>,
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::needless_return)]
 impl ChunkType {
-    fn bytes(&self) -> [u8; 4] {
+    /// Chunk types defined by the PNG specification.
+    pub const STANDARD_CHUNK_TYPES: [&'static str; 22] = [
+        "IHDR", "PLTE", "IDAT", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP",
+        "mDCV", "cLLI", "tEXt", "zTXt", "iTXt", "bKGD", "hIST", "pHYs", "sPLT", "eXIf", "tIME",
+    ];
+
+    pub fn bytes(&self) -> [u8; 4] {
         let mut result: [u8; 4] = [0; 4];
         result[0] = self.chunk_type[0];
         result[1] = self.chunk_type[1];
@@ -18,7 +28,7 @@ impl ChunkType {
         result
     }
 
-    fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool {
         let array = self.chunk_type.iter();
         for i in array {
             if !i.is_ascii_uppercase() && !i.is_ascii_lowercase() {
@@ -31,33 +41,85 @@ impl ChunkType {
         return true;
     }
 
-    fn is_safe_to_copy(&self) -> bool {
+    pub fn is_safe_to_copy(&self) -> bool {
         if self.chunk_type[3].is_ascii_lowercase() {
             return true;
         }
         return false;
     }
 
-    fn is_reserved_bit_valid(&self) -> bool {
+    pub fn is_reserved_bit_valid(&self) -> bool {
         if self.chunk_type[2].is_ascii_uppercase() {
             return true;
         }
         return false;
     }
 
-    fn is_public(&self) -> bool {
+    pub fn is_public(&self) -> bool {
         if self.chunk_type[1].is_ascii_uppercase() {
             return true;
         }
         return false;
     }
 
-    fn is_critical(&self) -> bool {
+    pub fn is_critical(&self) -> bool {
         if self.chunk_type[0].is_ascii_uppercase() {
             return true;
         }
         return false;
     }
+
+    /// Returns true if this chunk type is defined by the PNG specification.
+    pub fn is_standard(&self) -> bool {
+        Self::STANDARD_CHUNK_TYPES.contains(&self.to_string().as_str())
+    }
+
+    /// Private chunk types written by real software, which don't stand out in an image.
+    /// Only types no shipping decoder interprets are used, so filling them with other
+    /// data can't change how the image renders. These come from Adobe Fireworks, which
+    /// is discontinued. Apple's iDOT, Android's npTc and ImageMagick's vpAg, caNv and
+    /// orNT are left out because current decoders read them.
+    pub const MIMIC_CHUNK_TYPES: [&'static str; 5] = ["mkBF", "mkTS", "mkBS", "mkBT", "prVW"];
+
+    /// Derives a private, ancillary, safe to copy chunk type from a passphrase, so the
+    /// same passphrase finds the chunk again without naming its type.
+    pub fn from_passphrase(passphrase: &str) -> Self {
+        let mut digest = Sha256::digest(passphrase.as_bytes());
+        loop {
+            // Bytes from 234 up would favour the first letters, so they are skipped
+            let letters: Vec<u8> = digest.iter().filter(|&&b| b < 234).map(|b| b % 26).collect();
+            if let Some(letters) = letters.get(..4) {
+                return Self::private_from_letters([letters[0], letters[1], letters[2], letters[3]]);
+            }
+            digest = Sha256::digest(digest);
+        }
+    }
+
+    /// A random private, ancillary, safe to copy chunk type.
+    pub fn random() -> Result<Self, Box<dyn Error>> {
+        let mut letters = [0; 4];
+        for letter in &mut letters {
+            *letter = crypto::random_below(26)? as u8;
+        }
+        Ok(Self::private_from_letters(letters))
+    }
+
+    /// A random one of `MIMIC_CHUNK_TYPES`.
+    pub fn mimic() -> Result<Self, Box<dyn Error>> {
+        let names = Self::MIMIC_CHUNK_TYPES;
+        Self::from_str(names[crypto::random_below(names.len())?])
+    }
+
+    /// Maps each index below 26 to a letter, lowercase except for the reserved bit.
+    fn private_from_letters(indexes: [u8; 4]) -> Self {
+        let letters = indexes.iter().enumerate().map(|(i, index)| {
+            let base = if i == 2 { b'A' } else { b'a' };
+            base + index
+        });
+        Self {
+            chunk_type: letters.collect(),
+        }
+    }
 }
 
 impl Display for ChunkType {
@@ -71,10 +133,21 @@ impl Display for ChunkType {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for ChunkType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for ChunkType {
     type Err = Box<dyn Error>;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 4 {
+            return Err("Chunk Type must be exactly 4 characters long".into());
+        }
+
         let mut result: Vec<u8> = Vec::new();
 
         for character in s.chars() {
@@ -187,6 +260,16 @@ mod tests {
 
         let chunk = ChunkType::from_str("Ru1t");
         assert!(chunk.is_err());
+
+        let chunk = ChunkType::from_str("RuStY");
+        assert!(chunk.is_err());
+    }
+
+    #[test]
+    pub fn test_chunk_type_is_standard() {
+        assert!(ChunkType::from_str("tEXt").unwrap().is_standard());
+        assert!(ChunkType::from_str("IDAT").unwrap().is_standard());
+        assert!(!ChunkType::from_str("RuSt").unwrap().is_standard());
     }
 
     #[test]
@@ -202,4 +285,29 @@ mod tests {
         let _chunk_string = format!("{}", chunk_type_1);
         let _are_chunks_equal = chunk_type_1 == chunk_type_2;
     }
+
+    #[test]
+    pub fn test_chunk_type_from_passphrase() {
+        let chunk = ChunkType::from_passphrase("correct horse");
+        assert_eq!(chunk, ChunkType::from_passphrase("correct horse"));
+        assert_ne!(chunk, ChunkType::from_passphrase("battery staple"));
+        assert!(chunk.is_valid());
+        assert!(!chunk.is_critical());
+        assert!(!chunk.is_public());
+        assert!(chunk.is_safe_to_copy());
+    }
+
+    #[test]
+    pub fn test_random_and_mimic_chunk_types() {
+        let chunk = ChunkType::random().unwrap();
+        assert!(chunk.is_valid() && !chunk.is_critical() && !chunk.is_public());
+        assert!(chunk.is_safe_to_copy());
+
+        for name in ChunkType::MIMIC_CHUNK_TYPES {
+            let chunk = ChunkType::from_str(name).unwrap();
+            assert!(chunk.is_valid() && !chunk.is_critical() && !chunk.is_standard());
+        }
+        let chunk = ChunkType::mimic().unwrap();
+        assert!(ChunkType::MIMIC_CHUNK_TYPES.contains(&chunk.to_string().as_str()));
+    }
 }
@@ -1,105 +1,1667 @@
+use std::collections::HashMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use crate::args::{DecodeArgs, EncodeArgs, PrintArgs, RemoveArgs};
+use crate::args::{
+    CarveArgs, CombineDecodeArgs, DecodeArgs, DiffArgs, DumpArgs, EncodeArgs, ExtractArgs,
+    InjectArgs, KeygenArgs, ListMessagesArgs, NormalizeArgs, PrintArgs, RemoveArgs, RepairArgs,
+    SplitEncodeArgs, StripArgs, TrailerArgs,
+};
+use crate::atomic::{self, WriteOptions};
+use crate::batch;
 use crate::chunk::Chunk;
 use crate::chunk_type::ChunkType;
-use crate::png::Png;
+use crate::crypto::{self, Identity, Recipient};
+use crate::diff::{self as png_diff, ChunkChange, PixelComparison};
+use crate::encoding::PayloadEncoding;
+use crate::output::{self, OutputFormat};
+use crate::payload::{Encryption, Payload, PayloadKind};
+use crate::png::{ChunkPosition, NormalizeOptions, ParseDiagnostic, Png, StripPreset};
+use crate::report::{
+    DecodeAllReport, DecodeReport, FoundMessage, MessageListReport, MessageSummary, PrintReport,
+};
+use crate::scan::{self, ScannedChunk};
+use crate::shamir::{self, Share};
+use crate::stdio;
+use crate::text_chunk::TextChunk;
 use crate::Result;
 
-/// Encodes a message into a PNG file and saves the Result
+/// Encodes a message into each PNG file and saves the result
 pub fn encode(args: EncodeArgs) -> Result<()> {
-    let filename = args.filepath;
-    let chunk_type = args.chunk_type;
-    let message = args.message;
-    let out_filename = args.out_file;
+    let paths = batch::expand_paths(&args.filepath, args.recursive)?;
+    if args.out_file.is_some() && paths.len() > 1 {
+        return Err(
+            "--out-file can only be used when encoding a single file, use --out-dir".into(),
+        );
+    }
+    if args.out_file.is_none() {
+        // Files are encoded in parallel, so two results for one path would race
+        let outputs: Vec<PathBuf> = paths
+            .iter()
+            .filter_map(|path| {
+                renamed_output(path, args.out_dir.as_deref(), args.name_template.as_deref()).ok()
+            })
+            .collect();
+        if let Some(output) = batch::colliding_output(&outputs) {
+            return Err(format!(
+                "Two files would be saved to '{}', the input files need different names",
+                output.display()
+            )
+            .into());
+        }
+    }
+
+    let message = args.encoding.encode(args.message.as_bytes())?;
+    let decoy = match &args.decoy_message {
+        Some(decoy) => Some(args.encoding.encode(decoy.as_bytes())?),
+        None => None,
+    };
+    let recipients = read_recipients(&args.recipient)?;
+
+    // The password of the message and then that of the decoy, if there is one
+    let mut passwords = Vec::new();
+    if args.password || args.password_file.is_some() {
+        passwords.push(stdio::read_password(
+            "Password: ",
+            "PNGME_PASSWORD",
+            args.password_file.as_deref(),
+        )?);
+    }
+    if args.decoy_password || args.decoy_password_file.is_some() {
+        passwords.push(stdio::read_password(
+            "Decoy password: ",
+            "PNGME_DECOY_PASSWORD",
+            args.decoy_password_file.as_deref(),
+        )?);
+    }
+
+    if let (Some(out_dir), false) = (&args.out_dir, args.dry_run) {
+        fs::create_dir_all(out_dir)?;
+    }
+
+    batch::run(&paths, OutputFormat::Text, |path| {
+        encode_file(path, &args, &message, decoy.as_deref(), &recipients, &passwords)
+    })
+}
 
-    let file = fs::read(&filename.as_str())?;
+fn encode_file(
+    path: &Path,
+    args: &EncodeArgs,
+    message: &str,
+    decoy: Option<&str>,
+    recipients: &[Recipient],
+    passwords: &[String],
+) -> Result<String> {
+    let file = stdio::read_input(path)?;
 
     let mut png = Png::try_from(file.as_slice())?;
 
-    let chunk_type = ChunkType::from_str(&chunk_type)?;
-    let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
+    let chunk_type = match (&args.chunk_type, &args.type_passphrase) {
+        (Some(chunk_type), _) => chunk_type.clone(),
+        (None, Some(passphrase)) => ChunkType::from_passphrase(passphrase).to_string(),
+        (None, None) if args.mimic => ChunkType::mimic()?.to_string(),
+        (None, None) => ChunkType::random()?.to_string(),
+    };
+
+    let chunk = if let Some(keyword) = &args.keyword {
+        let text = if recipients.is_empty() && passwords.is_empty() {
+            message.to_string()
+        } else {
+            // Encrypt first and encode the ciphertext afterwards, so it is valid text.
+            // `Args::validate` made sure the encoding isn't plain
+            let decoy = args.decoy_message.as_deref().map(str::as_bytes);
+            let payload =
+                message_payload(args, args.message.as_bytes(), decoy, recipients, passwords)?;
+            args.encoding.encode(&payload.as_bytes())?
+        };
+        TextChunk::new(&chunk_type, keyword.clone(), text)?.to_chunk()?
+    } else {
+        let chunk_type = ChunkType::from_str(&chunk_type)?;
+        let decoy = decoy.map(str::as_bytes);
+        let payload = message_payload(args, message.as_bytes(), decoy, recipients, passwords)?;
+        let data = match &args.type_passphrase {
+            Some(passphrase) => crypto::seal(&payload.as_bytes(), passphrase)?,
+            None => payload.as_bytes(),
+        };
+        Chunk::new(chunk_type, data)
+    };
+
+    // Anything after IEND is read back as trailing data, so keep the chunk before it
+    png.insert_chunk_at(ChunkPosition::BeforeIend, chunk)?;
 
-    png.append_chunk(chunk);
+    let out_filename = match &args.out_file {
+        Some(out_file) => PathBuf::from(out_file),
+        None => renamed_output(path, args.out_dir.as_deref(), args.name_template.as_deref())?,
+    };
+    let in_place = out_filename == path;
 
-    fs::write(out_filename, png.as_bytes())?;
+    if args.dry_run {
+        return dry_run_report(&file, &png, &out_filename.to_string_lossy());
+    }
+    stdio::write_output(&out_filename, &png.as_bytes(), in_place, &args.write)?;
 
+    // A random type can't be derived again, so say which one was picked
+    if stdio::is_stdio(&out_filename) {
+        if args.random_type || args.mimic {
+            eprintln!("Encoded the message in a '{}' chunk", chunk_type);
+        }
+        return Ok(String::new());
+    }
+    if args.random_type || args.mimic {
+        return Ok(format!(
+            "Encoded the message in a '{}' chunk and saved the result to '{}'\n",
+            chunk_type,
+            out_filename.display()
+        ));
+    }
+    Ok(format!(
+        "Encoded the message and saved the result to '{}'\n",
+        out_filename.display()
+    ))
+}
+
+/// Wraps `message` in a payload, encrypted with `passwords` or to `recipients` if
+/// any are given. A `decoy` is stored alongside under the second password.
+fn message_payload(
+    args: &EncodeArgs,
+    message: &[u8],
+    decoy: Option<&[u8]>,
+    recipients: &[Recipient],
+    passwords: &[String],
+) -> Result<Payload> {
+    if !passwords.is_empty() {
+        let messages: Vec<(&[u8], &str)> = [Some(message), decoy]
+            .into_iter()
+            .flatten()
+            .zip(passwords)
+            .map(|(message, password)| (message, password.as_str()))
+            .collect();
+        let body = crypto::encrypt_deniable(&messages)?;
+        Payload::new(args.label.clone(), Encryption::Password, body)
+    } else if recipients.is_empty() {
+        Payload::new(args.label.clone(), Encryption::None, message.to_vec())
+    } else {
+        let body = crypto::encrypt(message, recipients)?;
+        Payload::new(args.label.clone(), Encryption::Recipients, body)
+    }
+}
+
+/// Returns where to save `path` after changing it: the file itself, unless an output
+/// directory or naming template asks for a new file
+fn renamed_output(
+    path: &Path,
+    out_dir: Option<&str>,
+    name_template: Option<&str>,
+) -> Result<PathBuf> {
+    if out_dir.is_none() && name_template.is_none() {
+        return Ok(path.to_path_buf());
+    }
+    if stdio::is_stdio(path) {
+        return Err("--out-dir and --name-template need a named input file".into());
+    }
+    let template = name_template.unwrap_or(batch::DEFAULT_NAME_TEMPLATE);
+    batch::output_path(path, template, out_dir.map(Path::new))
+}
+
+/// Splits a message into shares and hides one share in each PNG file
+pub fn split_encode(args: SplitEncodeArgs) -> Result<()> {
+    // `Args::validate` checked that there is one file per share
+    let shares = args.filepaths.len() as u8;
+
+    let split = shamir::split(args.message.as_bytes(), shares, args.threshold)?;
+
+    // Every file is read and checked before any is written, so a bad file doesn't
+    // leave the shares half written
+    let mut outputs = Vec::new();
+    for (filepath, share) in args.filepaths.iter().zip(&split) {
+        let path = Path::new(filepath);
+        let file = fs::read(path)?;
+        let mut png = Png::try_from(file.as_slice()).map_err(|e| format!("{}: {}", filepath, e))?;
+
+        let payload = Payload::new_share(args.label.clone(), share)?;
+        png.insert_chunk_at(
+            ChunkPosition::BeforeIend,
+            Chunk::new(ChunkType::from_str(&args.chunk_type)?, payload.as_bytes()),
+        )?;
+
+        let out_filename =
+            renamed_output(path, args.out_dir.as_deref(), args.name_template.as_deref())?;
+        if outputs.iter().any(|(_, _, other)| *other == out_filename) {
+            return Err(format!(
+                "Two shares would be saved to '{}', the input files need different names",
+                out_filename.display()
+            )
+            .into());
+        }
+        outputs.push((file, png, out_filename));
+    }
+
+    if let (Some(out_dir), false) = (&args.out_dir, args.dry_run) {
+        fs::create_dir_all(out_dir)?;
+    }
+
+    for (index, (file, png, out_filename)) in outputs.iter().enumerate() {
+        if args.dry_run {
+            print!(
+                "{}",
+                dry_run_report(file, png, &out_filename.to_string_lossy())?
+            );
+            continue;
+        }
+        let in_place = out_filename == Path::new(&args.filepaths[index]);
+        atomic::write_file(out_filename, &png.as_bytes(), in_place, &args.write)?;
+        println!(
+            "Saved share {} of {} to '{}'",
+            index + 1,
+            shares,
+            out_filename.display()
+        );
+    }
+    if !args.dry_run {
+        println!(
+            "Any {} of the {} files recover the message",
+            args.threshold, shares
+        );
+    }
     Ok(())
 }
 
-/// Searches for a message hidden in a PNG file and prints the message if one is found
+/// Recovers a message split with split-encode from the shares hidden in PNG files
+pub fn combine_decode(args: CombineDecodeArgs) -> Result<()> {
+    let mut shares: Vec<Share> = Vec::new();
+    for filepath in &args.filepaths {
+        let file = stdio::read_input(filepath)?;
+        let png = Png::try_from(file.as_slice()).map_err(|e| format!("{}: {}", filepath, e))?;
+
+        let trailer_chunks = scan::scan_chunks(png.trailer(), 0);
+        for (_, _, chunk) in candidate_chunks(&message_chunks(&png, &trailer_chunks, file.len())) {
+            if args
+                .chunk_type
+                .as_ref()
+                .is_some_and(|chunk_type| chunk.chunk_type().to_string() != *chunk_type)
+                || !Payload::has_magic(chunk.chunk_data())
+            {
+                continue;
+            }
+            let Ok(payload) = Payload::from_chunk_data(chunk.chunk_data()) else {
+                continue;
+            };
+            if payload.kind() != PayloadKind::Share
+                || args
+                    .label
+                    .as_ref()
+                    .is_some_and(|label| payload.label() != Some(label))
+            {
+                continue;
+            }
+            shares.push(Share::try_from(payload.body())?);
+        }
+    }
+
+    let mut set_ids: Vec<_> = shares.iter().map(|share| share.set_id).collect();
+    set_ids.sort();
+    set_ids.dedup();
+    match set_ids.len() {
+        0 => return Err("No shares were found in the files".into()),
+        1 => {}
+        sets => {
+            return Err(format!(
+                "The files hold shares of {} different messages, use --label or --chunk-type to pick one",
+                sets
+            )
+            .into())
+        }
+    }
+
+    let message = String::from_utf8(shamir::combine(&shares)?)?;
+    println!("Message: {}", message);
+    Ok(())
+}
+
+/// Searches for a message hidden in each PNG file and prints the message if one is found
 pub fn decode(args: DecodeArgs) -> Result<()> {
-    let filename = args.filepath;
-    let chunk_type = args.chunk_type;
+    let paths = batch::expand_paths(&args.filepath, args.recursive)?;
+    let password = if args.password || args.password_file.is_some() {
+        Some(stdio::read_password(
+            "Password: ",
+            "PNGME_PASSWORD",
+            args.password_file.as_deref(),
+        )?)
+    } else {
+        None
+    };
+    let credentials = Credentials {
+        identities: read_identities(&args.identity)?,
+        password,
+        type_passphrase: args.type_passphrase.clone(),
+    };
+    batch::run(&paths, args.format, |path| {
+        decode_file(path, &args, &credentials)
+    })
+}
 
-    let file = fs::read(&filename.as_str())?;
+/// The keys and passwords given to decrypt messages with
+struct Credentials {
+    identities: Vec<Identity>,
+    password: Option<String>,
+    /// Opens chunks that encode sealed with `crypto::seal`
+    type_passphrase: Option<String>,
+}
+
+fn decode_file(path: &Path, args: &DecodeArgs, credentials: &Credentials) -> Result<String> {
+    let file = stdio::read_input(path)?;
 
     let png = Png::try_from(file.as_slice())?;
 
-    let chunk = png
-        .chunks()
+    let trailer_chunks = scan::scan_chunks(png.trailer(), 0);
+    let chunks = message_chunks(&png, &trailer_chunks, file.len());
+
+    if args.auto {
+        let report = DecodeAllReport {
+            messages: auto_messages(&chunks, args.encoding, credentials),
+        };
+        if report.messages.is_empty() {
+            return Err("No messages were found in the file".into());
+        }
+        return Ok(format!("{}\n", output::render(&report, args.format)?));
+    }
+
+    // clap requires one of these when --auto is absent
+    let chunk_type = match (&args.chunk_type, &args.type_passphrase) {
+        (Some(chunk_type), _) => chunk_type.clone(),
+        (None, Some(passphrase)) => ChunkType::from_passphrase(passphrase).to_string(),
+        (None, None) => return Err("Missing chunk type".into()),
+    };
+
+    let stored = typed_messages(
+        &chunks,
+        &chunk_type,
+        args.keyword.as_deref(),
+        args.label.as_deref(),
+        args.encoding,
+        credentials,
+    );
+
+    if args.all {
+        let messages = stored
+            .into_iter()
+            .map(|(index, offset, stored)| {
+                let label = stored.as_ref().ok().and_then(|s| s.label.clone());
+                let message = stored.map(|s| s.message);
+                FoundMessage {
+                    index,
+                    offset,
+                    chunk_type: chunk_type.clone(),
+                    label,
+                    error: message.as_ref().err().map(|e| e.to_string()),
+                    message: message.ok(),
+                }
+            })
+            .collect();
+        let report = DecodeAllReport { messages };
+        if report.messages.is_empty() {
+            return Err(format!("No chunk of type '{}' was found in the file", chunk_type).into());
+        }
+        return Ok(format!("{}\n", output::render(&report, args.format)?));
+    }
+
+    let found = match args.index {
+        Some(index) => stored.into_iter().find(|(i, _, _)| *i == index),
+        None => stored.into_iter().next(),
+    };
+    let Some((_, _, stored)) = found else {
+        let mut error = format!("No chunk of type '{}'", chunk_type);
+        if let Some(index) = args.index {
+            error.push_str(&format!(" at index {}", index));
+        }
+        if let Some(keyword) = &args.keyword {
+            error.push_str(&format!(" with keyword '{}'", keyword));
+        }
+        if let Some(label) = &args.label {
+            error.push_str(&format!(" labelled '{}'", label));
+        }
+        error.push_str(" was found in the file");
+        return Err(error.into());
+    };
+    let message = stored?.message;
+
+    let report = DecodeReport {
+        chunk_type,
+        keyword: args.keyword.clone(),
+        label: args.label.clone(),
+        index: args.index,
+        message,
+    };
+    Ok(format!("{}\n", output::render(&report, args.format)?))
+}
+
+/// The messages in chunks of `chunk_type` that match `keyword` and `label`, with
+/// each chunk's index among all chunks of that type and its offset.
+fn typed_messages(
+    chunks: &[(usize, &Chunk)],
+    chunk_type: &str,
+    keyword: Option<&str>,
+    label: Option<&str>,
+    encoding: PayloadEncoding,
+    credentials: &Credentials,
+) -> Vec<(usize, usize, Result<StoredMessage>)> {
+    chunks
         .iter()
-        .find(|c| c.chunk_type().to_string() == chunk_type);
+        .filter(|(_, chunk)| chunk.chunk_type().to_string() == chunk_type)
+        .enumerate()
+        .filter_map(|(index, (offset, chunk))| {
+            stored_message(chunk, keyword, encoding, credentials)
+                .map(|stored| (index, *offset, stored))
+        })
+        .filter(|(_, _, stored)| match label {
+            Some(label) => stored
+                .as_ref()
+                .is_ok_and(|stored| stored.label.as_deref() == Some(label)),
+            None => true,
+        })
+        .collect()
+}
+
+/// Every chunk that may hold a message with its offset from the start of the file.
+/// Older versions of pngme appended the chunk after IEND, where it now ends up in
+/// the trailer, so `trailer_chunks` found there are included.
+fn message_chunks<'a>(
+    png: &'a Png,
+    trailer_chunks: &'a [ScannedChunk],
+    file_size: usize,
+) -> Vec<(usize, &'a Chunk)> {
+    let trailer_offset = file_size - png.trailer().len();
+    png.chunk_offsets()
+        .into_iter()
+        .zip(png.chunks())
+        .chain(
+            trailer_chunks
+                .iter()
+                .map(|scanned| (trailer_offset + scanned.offset, &scanned.chunk)),
+        )
+        .collect()
+}
+
+/// A message read from a chunk, decrypted and with its encoding undone
+struct StoredMessage {
+    label: Option<String>,
+    message: String,
+}
+
+/// Reads the message stored in `chunk`. With a `keyword` only text chunks under that
+/// keyword hold one, and `None` is returned for any other chunk.
+fn stored_message(
+    chunk: &Chunk,
+    keyword: Option<&str>,
+    encoding: PayloadEncoding,
+    credentials: &Credentials,
+) -> Option<Result<StoredMessage>> {
+    match keyword {
+        Some(keyword) => TextChunk::try_from(chunk)
+            .ok()
+            .filter(|text_chunk| text_chunk.keyword() == keyword)
+            .map(|text_chunk| read_text_message(text_chunk.text(), encoding, credentials)),
+        None => Some(read_payload(chunk, encoding, credentials)),
+    }
+}
+
+/// Reads the message in a text chunk. An encrypted message is a payload that was
+/// encrypted first and encoded afterwards, so its encoding is undone before it is
+/// decrypted.
+fn read_text_message(
+    text: &str,
+    encoding: PayloadEncoding,
+    credentials: &Credentials,
+) -> Result<StoredMessage> {
+    let data = encoding.decode(text)?;
+    if encoding != PayloadEncoding::Plain && Payload::has_magic(&data) {
+        let payload = Payload::try_from(data.as_slice())?;
+        return Ok(StoredMessage {
+            label: payload.label().map(String::from),
+            message: String::from_utf8(decrypt_body(&payload, credentials)?)?,
+        });
+    }
+    Ok(StoredMessage {
+        label: None,
+        message: String::from_utf8(data)?,
+    })
+}
+
+/// Reads the payload in `chunk`, decrypting it with `credentials` if it is encrypted
+fn read_payload(
+    chunk: &Chunk,
+    encoding: PayloadEncoding,
+    credentials: &Credentials,
+) -> Result<StoredMessage> {
+    let payload = match &credentials.type_passphrase {
+        Some(passphrase) => {
+            Payload::try_from(crypto::open(chunk.chunk_data(), passphrase)?.as_slice())?
+        }
+        None => Payload::from_chunk_data(chunk.chunk_data())?,
+    };
+    let body = decrypt_body(&payload, credentials)?;
+    Ok(StoredMessage {
+        label: payload.label().map(String::from),
+        message: decode_message(&String::from_utf8(body)?, encoding)?,
+    })
+}
+
+/// The body of a message payload, decrypted with `credentials` if it is encrypted
+fn decrypt_body(payload: &Payload, credentials: &Credentials) -> Result<Vec<u8>> {
+    if payload.kind() == PayloadKind::Share {
+        return Err("The chunk holds a share of a split message, use combine-decode".into());
+    }
+    Ok(match payload.encryption() {
+        Encryption::None => payload.body().to_vec(),
+        Encryption::Recipients if credentials.identities.is_empty() => {
+            return Err("The message is encrypted, use --identity to decrypt it".into())
+        }
+        Encryption::Recipients => crypto::decrypt(payload.body(), &credentials.identities)?,
+        Encryption::Password => match &credentials.password {
+            Some(password) => crypto::decrypt_deniable(payload.body(), password)?,
+            None => return Err("The message is encrypted, use --password to decrypt it".into()),
+        },
+    })
+}
+
+/// Reads each public key given on the command line, or every key listed in a file
+fn read_recipients(recipients: &[String]) -> Result<Vec<Recipient>> {
+    let mut keys = Vec::new();
+    for recipient in recipients {
+        if recipient.starts_with(crypto::RECIPIENT_PREFIX) {
+            keys.push(Recipient::from_str(recipient)?);
+            continue;
+        }
+
+        let file = fs::read_to_string(recipient).map_err(|e| {
+            format!(
+                "'{}' is not a public key or a readable file: {}",
+                recipient, e
+            )
+        })?;
+        for line in file.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') {
+                keys.push(Recipient::from_str(line)?);
+            }
+        }
+    }
+    Ok(keys)
+}
+
+fn read_identities(paths: &[String]) -> Result<Vec<Identity>> {
+    let mut identities = Vec::new();
+    for path in paths {
+        identities.extend(Identity::parse_file(&fs::read_to_string(path)?)?);
+    }
+    Ok(identities)
+}
+
+fn decode_message(text: &str, encoding: PayloadEncoding) -> Result<String> {
+    Ok(String::from_utf8(encoding.decode(text)?)?)
+}
+
+/// Counts each chunk's position among the chunks of its type, skipping critical
+/// chunks and public ones the PNG specification defines, which never hold messages.
+fn candidate_chunks<'a>(chunks: &[(usize, &'a Chunk)]) -> Vec<(usize, usize, &'a Chunk)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut candidates = Vec::new();
+
+    for (offset, chunk) in chunks {
+        let chunk_type = chunk.chunk_type();
+        let count = counts.entry(chunk_type.to_string()).or_default();
+        let index = *count;
+        *count += 1;
+
+        if chunk_type.is_critical() || (chunk_type.is_public() && chunk_type.is_standard()) {
+            continue;
+        }
+        candidates.push((index, *offset, *chunk));
+    }
+    candidates
+}
 
-    if let Some(chunk) = chunk {
-        let message = String::from_utf8(chunk.chunk_data().to_vec())?;
-        println!("Message: {}", message);
+/// Finds every private or unknown ancillary chunk holding a pngme payload.
+///
+/// Messages written by older versions of pngme have no payload magic, so those are
+/// found by whether their data decodes to readable text instead.
+fn auto_messages(
+    chunks: &[(usize, &Chunk)],
+    encoding: PayloadEncoding,
+    credentials: &Credentials,
+) -> Vec<FoundMessage> {
+    let mut messages = Vec::new();
+
+    for (index, offset, chunk) in candidate_chunks(chunks) {
+        let has_magic = Payload::has_magic(chunk.chunk_data());
+        let stored = read_payload(chunk, encoding, credentials);
+        let label = stored.as_ref().ok().and_then(|s| s.label.clone());
+        let message = stored.map(|s| s.message);
+
+        let readable = message.as_ref().is_ok_and(|message| is_readable(message));
+        if has_magic || readable {
+            messages.push(FoundMessage {
+                index,
+                offset,
+                chunk_type: chunk.chunk_type().to_string(),
+                label,
+                error: message.as_ref().err().map(|e| e.to_string()),
+                message: message.ok(),
+            });
+        }
+    }
+    messages
+}
+
+/// Returns true if `text` looks like a message rather than binary data.
+fn is_readable(text: &str) -> bool {
+    !text.is_empty() && !text.chars().any(|c| c.is_control() && !c.is_whitespace())
+}
+
+/// Removes chunks from a PNG file, prints what was removed and saves the result
+pub fn remove(args: RemoveArgs) -> Result<()> {
+    let filename = args.filepath;
+
+    let file: Vec<u8> = fs::read(&filename)?;
+    let mut png = Png::try_from(file.as_slice())?;
+
+    let matches = |chunk: &Chunk| {
+        args.chunk_type
+            .as_ref()
+            .is_none_or(|chunk_type| chunk.chunk_type().to_string() == *chunk_type)
+            && (!args.ancillary || !chunk.chunk_type().is_critical())
+            && (!args.private || !chunk.chunk_type().is_public())
+            && (!args.unknown || !chunk.chunk_type().is_standard())
+            && args.label.as_ref().is_none_or(|label| {
+                Payload::has_magic(chunk.chunk_data())
+                    && Payload::from_chunk_data(chunk.chunk_data())
+                        .is_ok_and(|payload| payload.label() == Some(label))
+            })
+    };
+    let filtered = args.ancillary || args.private || args.unknown || args.label.is_some();
+
+    let removed: Vec<Chunk> = if let Some(index) = args.index {
+        // The index counts only the chunks that match the other options
+        let position = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| matches(chunk))
+            .nth(index)
+            .map(|(position, _)| position);
+        match position {
+            Some(position) => vec![png.remove_at(position)?],
+            None => vec![],
+        }
+    } else if args.all || filtered {
+        png.retain(|chunk| !matches(chunk))
     } else {
-        println!(
-            "Error: No chunk of type '{}' was found in the file",
-            chunk_type
-        );
+        match png.chunks().iter().position(matches) {
+            Some(position) => vec![png.remove_at(position)?],
+            None => vec![],
+        }
+    };
+
+    if removed.is_empty() {
+        match &args.chunk_type {
+            Some(chunk_type) => println!(
+                "Warning: No matching chunk of type '{}' was found in the file",
+                chunk_type
+            ),
+            None => println!("Warning: No matching chunk was found in the file"),
+        }
+        return Ok(());
     }
 
+    if args.dry_run {
+        print!("{}", dry_run_report(&file, &png, &filename)?);
+        return Ok(());
+    }
+
+    // Write the complete PNG data back to file
+    atomic::write_file(Path::new(&filename), &png.as_bytes(), true, &args.write)?;
+
+    println!(
+        "Successfully removed {} chunk(s) from the file:",
+        removed.len()
+    );
+    for (chunk_type, count) in count_by_type(&removed) {
+        println!("  {} x{}", chunk_type, count);
+    }
     Ok(())
 }
 
-/// Removes a chunk from a PNG file and saves the result
-pub fn remove(args: RemoveArgs) -> Result<()> {
+/// Removes metadata chunks selected by a preset from each file and saves the result
+pub fn strip(args: StripArgs) -> Result<()> {
+    let paths = batch::expand_paths(&args.filepath, args.recursive)?;
+    if args.out_file.is_some() && paths.len() > 1 {
+        return Err("--out-file can only be used when stripping a single file".into());
+    }
+
+    let preset = if args.all {
+        StripPreset::All
+    } else if args.privacy {
+        StripPreset::Privacy
+    } else {
+        StripPreset::KeepColor
+    };
+
+    batch::run(&paths, OutputFormat::Text, |path| strip_file(path, &args, preset))
+}
+
+fn strip_file(path: &Path, args: &StripArgs, preset: StripPreset) -> Result<String> {
+    let file: Vec<u8> = fs::read(path)?;
+    let mut png = Png::try_from(file.as_slice())?;
+
+    let removed = png.strip(preset, &args.keep, args.unsafe_to_copy);
+
+    if removed.is_empty() {
+        return Ok(String::from(
+            "Nothing to strip, the file was left unchanged\n",
+        ));
+    }
+
+    let out_filename = args
+        .out_file
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| path.to_path_buf());
+    if args.dry_run {
+        return dry_run_report(&file, &png, &out_filename.to_string_lossy());
+    }
+    let in_place = args.out_file.is_none();
+    atomic::write_file(&out_filename, &png.as_bytes(), in_place, &args.write)?;
+
+    let mut result = format!(
+        "Stripped {} chunk(s) and saved the result to '{}':\n",
+        removed.len(),
+        out_filename.display()
+    );
+    for (chunk_type, count) in count_by_type(&removed) {
+        result.push_str(&format!("  {} x{}\n", chunk_type, count));
+    }
+    Ok(result)
+}
+
+/// Rewrites a PNG file with its chunks in canonical order and saves the result
+pub fn normalize(args: NormalizeArgs) -> Result<()> {
     let filename = args.filepath;
 
     let file: Vec<u8> = fs::read(&filename)?;
     let mut png = Png::try_from(file.as_slice())?;
 
-    // Attempting to remove chunk
-    let chunk_type = args.chunk_type;
+    let report = png.normalize(NormalizeOptions {
+        idat_size: args.idat_size,
+        keep_unsafe: args.keep_unsafe,
+    });
+
+    if report.is_unchanged() {
+        println!("The file is already normalized and was left unchanged");
+        return Ok(());
+    }
+
+    let in_place = args.out_file.is_none();
+    let out_filename = args.out_file.unwrap_or(filename);
+    if args.dry_run {
+        print!("{}", dry_run_report(&file, &png, &out_filename)?);
+        return Ok(());
+    }
+    atomic::write_file(
+        Path::new(&out_filename),
+        &png.as_bytes(),
+        in_place,
+        &args.write,
+    )?;
+
+    println!(
+        "Normalized the file and saved the result to '{}':",
+        out_filename
+    );
+    if report.reordered {
+        println!("  Reordered chunks into canonical order");
+    }
+    let (idat_before, idat_after) = report.idat_chunks;
+    if report.idat_resplit {
+        println!(
+            "  Resplit image data from {} into {} IDAT chunk(s)",
+            idat_before, idat_after
+        );
+    }
+    if !report.trailer.is_empty() {
+        println!(
+            "  Dropped {} byte(s) of trailing data",
+            report.trailer.len()
+        );
+    }
+    for (label, chunks) in [
+        ("after IEND", &report.after_iend),
+        ("duplicated", &report.duplicates),
+        ("unsafe to copy", &report.unsafe_to_copy),
+    ] {
+        for (chunk_type, count) in count_by_type(chunks) {
+            println!("  Dropped {} x{} ({})", chunk_type, count, label);
+        }
+    }
+    Ok(())
+}
+
+/// Parses a damaged PNG file leniently, fixes what it can and saves the result
+pub fn repair(args: RepairArgs) -> Result<()> {
+    let filename = args.filepath;
+
+    let file: Vec<u8> = fs::read(&filename)?;
+    let (mut png, diagnostics) = Png::try_from_lenient(file.as_slice())?;
+
+    if diagnostics.is_empty() {
+        println!("No problems were found, the file was left unchanged");
+        return Ok(());
+    }
+
+    println!("Found {} problem(s):", diagnostics.len());
+    for diagnostic in &diagnostics {
+        println!("  {}", diagnostic);
+    }
 
-    // Check if chunk exists before trying to remove it
-    let chunk_exists = png
-        .chunks()
+    let repaired = png.repair_crcs(args.ancillary_only);
+    let unrepaired = diagnostics
         .iter()
-        .any(|c| c.chunk_type().to_string() == chunk_type);
+        .filter(|d| matches!(d, ParseDiagnostic::CrcMismatch { .. }))
+        .count()
+        - repaired.len();
 
-    if !chunk_exists {
+    if diagnostics.contains(&ParseDiagnostic::MissingIend) {
+        png.append_chunk(Chunk::new(ChunkType::from_str("IEND")?, Vec::new()));
+        println!("Appended a missing IEND chunk");
+    }
+    println!("Recomputed {} CRC(s)", repaired.len());
+    let trailer = png.set_trailer(Vec::new());
+    if !trailer.is_empty() {
+        println!("Truncated {} byte(s) of trailing data", trailer.len());
+    }
+    if unrepaired > 0 {
         println!(
-            "Warning: No chunk of type '{}' was found in the file",
-            chunk_type
+            "Warning: Left {} critical chunk(s) with a bad CRC untouched",
+            unrepaired
         );
+    }
+
+    // The bytes of an incomplete chunk were never parsed, so they are dropped here
+    let in_place = args.out_file.is_none();
+    let out_filename = args.out_file.unwrap_or(filename);
+    if args.dry_run {
+        print!("{}", dry_run_report(&file, &png, &out_filename)?);
         return Ok(());
     }
+    atomic::write_file(
+        Path::new(&out_filename),
+        &png.as_bytes(),
+        in_place,
+        &args.write,
+    )?;
+
+    println!("Saved the repaired file to '{}'", out_filename);
+    Ok(())
+}
 
-    png.remove_first_chunk(chunk_type.as_str())?;
+/// Writes every PNG found inside a file to its own file, salvaging truncated ones
+pub fn carve(args: CarveArgs) -> Result<()> {
+    let file: Vec<u8> = stdio::read_input(&args.filepath)?;
 
-    // Write the complete PNG data back to file
-    fs::write(filename, png.as_bytes())?;
+    let carved = scan::carve(&file);
+    if carved.is_empty() {
+        println!("Error: No PNG signature was found in the file");
+        return Ok(());
+    }
+
+    let stem = Path::new(&args.filepath)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("carved"));
+
+    for (index, mut carved_png) in carved.into_iter().enumerate() {
+        let has_iend = carved_png
+            .png
+            .chunks()
+            .last()
+            .is_some_and(|c| c.chunk_type().to_string() == "IEND");
+        if !has_iend {
+            carved_png
+                .png
+                .append_chunk(Chunk::new(ChunkType::from_str("IEND")?, Vec::new()));
+        }
+
+        let out_filename = Path::new(&args.out_dir).join(format!("{}-{}.png", stem, index));
+        let bytes = carved_png.png.as_bytes();
+        if args.dry_run {
+            println!(
+                "PNG at offset {:#010x}: {} chunk(s), would be saved to '{}' ({} bytes)",
+                carved_png.offset,
+                carved_png.png.chunks().len(),
+                out_filename.display(),
+                bytes.len()
+            );
+            continue;
+        }
+        atomic::write_file(&out_filename, &bytes, false, &args.write)?;
+
+        println!(
+            "PNG at offset {:#010x}: {} chunk(s){}, saved to '{}'",
+            carved_png.offset,
+            carved_png.png.chunks().len(),
+            if carved_png.complete {
+                ""
+            } else {
+                " salvaged from a damaged or truncated file"
+            },
+            out_filename.display()
+        );
+    }
+    Ok(())
+}
+
+/// Shows, extracts, appends to or removes the data after a PNG file's IEND chunk
+pub fn trailer(args: TrailerArgs) -> Result<()> {
+    let filename = args.filepath;
+
+    let file: Vec<u8> = fs::read(&filename)?;
+    let mut png = Png::try_from(file.as_slice())?;
+    let trailer_offset = file.len() - png.trailer().len();
+
+    if let Some(out) = &args.extract {
+        if args.dry_run {
+            println!(
+                "Dry run, nothing was written. '{}' would be {} bytes",
+                out,
+                png.trailer().len()
+            );
+            return Ok(());
+        }
+        atomic::write_file(Path::new(out), png.trailer(), false, &args.write)?;
+        println!("Wrote {} bytes to '{}'", png.trailer().len(), out);
+        return Ok(());
+    }
+
+    if let Some(append) = &args.append {
+        let mut trailer = png.trailer().to_vec();
+        trailer.extend(fs::read(append)?);
+        png.set_trailer(trailer);
+    } else if args.remove {
+        if png.trailer().is_empty() {
+            println!("There is no trailing data, the file was left unchanged");
+            return Ok(());
+        }
+        png.set_trailer(Vec::new());
+    } else {
+        if png.trailer().is_empty() {
+            println!("There is no trailing data after IEND");
+            return Ok(());
+        }
+
+        println!(
+            "{} bytes of trailing data at offset {:#010x}",
+            png.trailer().len(),
+            trailer_offset
+        );
+        for scanned in scan::scan_chunks(png.trailer(), 0) {
+            println!(
+                "  Contains chunk {} at offset {:#010x}",
+                scanned.chunk.chunk_type(),
+                trailer_offset + scanned.offset
+            );
+        }
+        for offset in scan::find_signatures(png.trailer()) {
+            println!(
+                "  Contains a PNG signature at offset {:#010x}",
+                trailer_offset + offset
+            );
+        }
+        print!("{}", hex_dump(png.trailer(), trailer_offset));
+        return Ok(());
+    }
+
+    let in_place = args.out_file.is_none();
+    let out_filename = args.out_file.unwrap_or(filename);
+    if args.dry_run {
+        print!("{}", dry_run_report(&file, &png, &out_filename)?);
+        return Ok(());
+    }
+    atomic::write_file(
+        Path::new(&out_filename),
+        &png.as_bytes(),
+        in_place,
+        &args.write,
+    )?;
 
     println!(
-        "Successfully removed chunk of type '{}' from the file",
-        chunk_type
+        "Trailing data is now {} bytes, saved the result to '{}'",
+        png.trailer().len(),
+        out_filename
     );
     Ok(())
 }
 
-/// Prints all of the chunks in a PNG file
+/// Prints the chunk level differences between two PNG files and whether their pixels match
+pub fn diff(args: DiffArgs) -> Result<()> {
+    let a_file: Vec<u8> = stdio::read_input(&args.a)?;
+    let b_file: Vec<u8> = stdio::read_input(&args.b)?;
+    let a = Png::try_from(a_file.as_slice())?;
+    let b = Png::try_from(b_file.as_slice())?;
+
+    let result = png_diff::diff(&a, &b);
+
+    println!("--- {}", args.a);
+    println!("+++ {}", args.b);
+    print!("{}", chunk_changes(&a, &b, &result.changes));
+
+    match result.pixels {
+        PixelComparison::Identical => println!("Pixel data: identical"),
+        PixelComparison::Different => println!("Pixel data: different"),
+        PixelComparison::Unknown(reason) => {
+            println!("Pixel data: could not be compared, {}", reason)
+        }
+    }
+    Ok(())
+}
+
+/// Lists every pngme payload in a PNG file with its label and size
+pub fn list_messages(args: ListMessagesArgs) -> Result<()> {
+    let file = stdio::read_input(&args.filepath)?;
+    let png = Png::try_from(file.as_slice())?;
+
+    let trailer_chunks = scan::scan_chunks(png.trailer(), 0);
+    let chunks = message_chunks(&png, &trailer_chunks, file.len());
+
+    let report = MessageListReport {
+        messages: message_summaries(&chunks),
+    };
+    println!("{}", output::render(&report, args.format)?);
+    Ok(())
+}
+
+/// Summarizes every pngme payload in `chunks`, including messages written by older
+/// versions of pngme, which are found by being readable text like in `auto_messages`.
+fn message_summaries(chunks: &[(usize, &Chunk)]) -> Vec<MessageSummary> {
+    candidate_chunks(chunks)
+        .into_iter()
+        .filter(|(_, _, chunk)| {
+            let data = chunk.chunk_data();
+            Payload::has_magic(data) || std::str::from_utf8(data).is_ok_and(is_readable)
+        })
+        .map(|(index, offset, chunk)| {
+            let payload = Payload::from_chunk_data(chunk.chunk_data());
+            MessageSummary {
+                index,
+                offset,
+                chunk_type: chunk.chunk_type().to_string(),
+                label: payload
+                    .as_ref()
+                    .ok()
+                    .and_then(|p| p.label().map(String::from)),
+                size: payload.as_ref().map(|p| p.body().len()).unwrap_or_default(),
+                encrypted: payload
+                    .as_ref()
+                    .is_ok_and(|p| p.encryption() != Encryption::None),
+                share: payload
+                    .as_ref()
+                    .is_ok_and(|p| p.kind() == PayloadKind::Share),
+                error: payload.err().map(|e| e.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Generates a key pair, saving the secret key and printing the public key
+pub fn keygen(args: KeygenArgs) -> Result<()> {
+    let identity = Identity::generate()?;
+    let recipient = identity.to_recipient();
+    let contents = format!("# public key: {}\n{}\n", recipient, identity);
+
+    let Some(out_file) = &args.out_file else {
+        print!("{}", contents);
+        return Ok(());
+    };
+
+    // Only the owner should be able to read a secret key, or a backup of an old one
+    let options = WriteOptions {
+        mode: Some(0o600),
+        ..args.write
+    };
+    atomic::write_file(Path::new(out_file), contents.as_bytes(), false, &options)?;
+
+    println!("Saved the secret key to '{}'", out_file);
+    println!("Public key: {}", recipient);
+    Ok(())
+}
+
+/// Lists the chunks added, removed, modified or moved between two PNG files
+fn chunk_changes(a: &Png, b: &Png, changes: &[ChunkChange]) -> String {
+    let mut lines = Vec::new();
+    let (mut added, mut removed, mut modified, mut reordered, mut unchanged) = (0, 0, 0, 0, 0);
+    for change in changes {
+        match change {
+            ChunkChange::Unchanged { .. } => unchanged += 1,
+            ChunkChange::Reordered { a, b, chunk } => {
+                reordered += 1;
+                lines.push(format!("> [{}] -> [{}] {} moved", a, b, chunk.chunk_type()));
+            }
+            ChunkChange::Modified {
+                a: i,
+                b: j,
+                old,
+                new,
+            } => {
+                modified += 1;
+                lines.push(format!(
+                    "~ [{}] -> [{}] {} modified",
+                    i,
+                    j,
+                    old.chunk_type()
+                ));
+                if old.length() != new.length() {
+                    lines.push(format!("    length {} -> {}", old.length(), new.length()));
+                }
+                for line in png_diff::field_differences(a, b, old, new) {
+                    lines.push(format!("    {}", line));
+                }
+            }
+            ChunkChange::Removed { a, chunk } => {
+                removed += 1;
+                lines.push(format!("- [{}] {} removed", a, chunk.chunk_type()));
+            }
+            ChunkChange::Added { b, chunk } => {
+                added += 1;
+                lines.push(format!("+ [{}] {} added", b, chunk.chunk_type()));
+            }
+        }
+    }
+    if a.trailer() != b.trailer() {
+        lines.push(format!(
+            "~ trailing data {} -> {} bytes",
+            a.trailer().len(),
+            b.trailer().len()
+        ));
+    }
+
+    lines.push(format!(
+        "{} added, {} removed, {} modified, {} reordered, {} unchanged",
+        added, removed, modified, reordered, unchanged
+    ));
+
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Describes what a mutating command would write, without writing it
+fn dry_run_report(original: &[u8], png: &Png, out_filename: &str) -> Result<String> {
+    let (before, _) = Png::try_from_lenient(original)?;
+    let bytes = png.as_bytes();
+
+    Ok(format!(
+        "Dry run, nothing was written. '{}' would be {} bytes (input is {} bytes):\n{}",
+        out_filename,
+        bytes.len(),
+        original.len(),
+        chunk_changes(&before, png, &png_diff::diff(&before, png).changes)
+    ))
+}
+
+/// Counts chunks per type, in order of first appearance
+fn count_by_type(chunks: &[Chunk]) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for chunk in chunks {
+        let chunk_type = chunk.chunk_type().to_string();
+        match counts.iter_mut().find(|(t, _)| *t == chunk_type) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((chunk_type, 1)),
+        }
+    }
+    counts
+}
+
+/// Prints a summary of each PNG file followed by a detailed view of each of its chunks
 pub fn print_chunks(args: PrintArgs) -> Result<()> {
-    let filename = args.filepath;
-    let file: Vec<u8> = fs::read(filename)?;
+    let paths = batch::expand_paths(&args.filepath, args.recursive)?;
+    batch::run(&paths, args.format, |path| {
+        let filename = path.to_string_lossy();
+        let file: Vec<u8> = stdio::read_input(path)?;
+        let png = Png::try_from(file.as_slice())?;
+
+        let report = PrintReport::new(&filename, file.len(), &png);
+        output::render(&report, args.format)
+    })
+}
+
+/// Prints a hex and ASCII dump of a chunk's data, labelled with file offsets
+pub fn dump(args: DumpArgs) -> Result<()> {
+    let file: Vec<u8> = stdio::read_input(&args.filepath)?;
     let png = Png::try_from(file.as_slice())?;
 
-    for chunk in png.chunks() {
-        println!("{}", chunk);
+    let Some((chunk, offset)) = png.nth_chunk_by_type(&args.chunk_type, args.index) else {
+        print_no_such_chunk(&args.chunk_type, args.index);
+        return Ok(());
+    };
+
+    println!(
+        "{} #{}: {} bytes of data at offset {:#010x}",
+        chunk.chunk_type(),
+        args.index,
+        chunk.length(),
+        offset + 8
+    );
+    // Chunk data starts after the 4 byte length and 4 byte type
+    print!("{}", hex_dump(chunk.chunk_data(), offset + 8));
+
+    Ok(())
+}
+
+/// Writes a chunk's data, or the whole chunk with `--raw`, to a file
+pub fn extract(args: ExtractArgs) -> Result<()> {
+    let file: Vec<u8> = stdio::read_input(&args.filepath)?;
+    let png = Png::try_from(file.as_slice())?;
+
+    let Some((chunk, _)) = png.nth_chunk_by_type(&args.chunk_type, args.index) else {
+        print_no_such_chunk(&args.chunk_type, args.index);
+        return Ok(());
+    };
+
+    let bytes = if args.raw {
+        chunk.as_bytes()
+    } else {
+        chunk.chunk_data().to_vec()
+    };
+    if args.dry_run {
+        println!(
+            "Dry run, nothing was written. '{}' would be {} bytes",
+            args.out,
+            bytes.len()
+        );
+        return Ok(());
     }
+    atomic::write_file(Path::new(&args.out), &bytes, false, &args.write)?;
 
+    println!("Wrote {} bytes to '{}'", bytes.len(), args.out);
     Ok(())
 }
+
+/// Inserts a chunk built from a data file, or a pre-built raw chunk, into a PNG file
+pub fn inject(args: InjectArgs) -> Result<()> {
+    let file: Vec<u8> = stdio::read_input(&args.filepath)?;
+    let mut png = Png::try_from(file.as_slice())?;
+
+    let chunk = if let Some(raw_chunk) = &args.raw_chunk {
+        let bytes = fs::read(raw_chunk)?;
+        // Chunk::try_from checks the CRC but trusts the slice, so check the length field too
+        let chunk = Chunk::try_from(bytes.as_slice())?;
+        let declared_length = u32::from_be_bytes(bytes[0..4].try_into()?);
+        if declared_length != chunk.length() {
+            return Err(format!(
+                "Chunk length field says {} bytes but it holds {}",
+                declared_length,
+                chunk.length()
+            )
+            .into());
+        }
+        chunk
+    } else {
+        // Both are required by clap when --raw-chunk is absent
+        let chunk_type = args.chunk_type.ok_or("Missing chunk type")?;
+        let data_file = args.data_file.ok_or("Missing data file")?;
+        Chunk::new(ChunkType::from_str(&chunk_type)?, fs::read(data_file)?)
+    };
+
+    if !chunk.chunk_type().is_valid() {
+        return Err(format!("'{}' is not a valid chunk type", chunk.chunk_type()).into());
+    }
+
+    let chunk_type = chunk.chunk_type().to_string();
+    let index = png.insert_chunk_at(args.position, chunk)?;
+    if args.dry_run {
+        print!("{}", dry_run_report(&file, &png, &args.out_file)?);
+        return Ok(());
+    }
+    stdio::write_output(&args.out_file, &png.as_bytes(), false, &args.write)?;
+
+    // Keep stdout clean when it carries the PNG
+    let summary = format!(
+        "Inserted chunk of type '{}' at index {} into '{}'",
+        chunk_type, index, args.out_file
+    );
+    if args.out_file == stdio::STDIO_PATH {
+        eprintln!("{}", summary);
+    } else {
+        println!("{}", summary);
+    }
+    Ok(())
+}
+
+fn print_no_such_chunk(chunk_type: &str, index: usize) {
+    println!(
+        "Error: No chunk of type '{}' at index {} was found in the file",
+        chunk_type, index
+    );
+}
+
+/// Formats `data` as lines of 16 hex bytes followed by their printable ASCII characters
+fn hex_dump(data: &[u8], start_offset: usize) -> String {
+    let mut result = String::new();
+
+    for (line, bytes) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = bytes
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+
+        result.push_str(&format!(
+            "{:08x}  {:<47}  |{}|\n",
+            start_offset + line * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("pngme-commands-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn no_credentials() -> Credentials {
+        Credentials {
+            identities: Vec::new(),
+            password: None,
+            type_passphrase: None,
+        }
+    }
+
+    fn payload_chunk(chunk_type: &str, label: Option<&str>, message: &str) -> Chunk {
+        let payload = Payload::new(
+            label.map(String::from),
+            Encryption::None,
+            message.as_bytes().to_vec(),
+        )
+        .unwrap();
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), payload.as_bytes())
+    }
+
+    fn text_chunk(keyword: &str, text: &str) -> Chunk {
+        TextChunk::new("tEXt", keyword.to_string(), text.to_string())
+            .unwrap()
+            .to_chunk()
+            .unwrap()
+    }
+
+    /// IHDR, two tEXt chunks, three ruSt messages, a binary private chunk and IEND,
+    /// each at an offset of ten times its index
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            Chunk::new(ChunkType::from_str("IHDR").unwrap(), vec![0; 13]),
+            text_chunk("Comment", "hello"),
+            payload_chunk("ruSt", Some("alice"), "one"),
+            text_chunk("secret", "hidden"),
+            payload_chunk("ruSt", Some("bob"), "two"),
+            Chunk::new(ChunkType::from_str("ruSt").unwrap(), b"three".to_vec()),
+            Chunk::new(ChunkType::from_str("prIv").unwrap(), vec![0, 1, 2]),
+            Chunk::new(ChunkType::from_str("IEND").unwrap(), Vec::new()),
+        ]
+    }
+
+    fn with_offsets(chunks: &[Chunk]) -> Vec<(usize, &Chunk)> {
+        chunks.iter().enumerate().map(|(i, c)| (i * 10, c)).collect()
+    }
+
+    fn messages(found: Vec<(usize, usize, Result<StoredMessage>)>) -> Vec<(usize, String)> {
+        found
+            .into_iter()
+            .map(|(index, _, stored)| (index, stored.unwrap().message))
+            .collect()
+    }
+
+    #[test]
+    fn test_typed_messages() {
+        let chunks = testing_chunks();
+        let chunks = with_offsets(&chunks);
+        let credentials = no_credentials();
+        let found = typed_messages(&chunks, "ruSt", None, None, PayloadEncoding::Plain, &credentials);
+        let offsets: Vec<usize> = found.iter().map(|(_, offset, _)| *offset).collect();
+        assert_eq!(offsets, [20, 40, 50]);
+        assert_eq!(
+            messages(found),
+            [(0, "one".into()), (1, "two".into()), (2, "three".into())]
+        );
+    }
+
+    #[test]
+    fn test_typed_messages_index_counts_every_chunk_of_the_type() {
+        let chunks = testing_chunks();
+        let chunks = with_offsets(&chunks);
+        let credentials = no_credentials();
+        let plain = PayloadEncoding::Plain;
+
+        let found = typed_messages(&chunks, "ruSt", None, Some("bob"), plain, &credentials);
+        assert_eq!(messages(found), [(1, "two".into())]);
+
+        let found = typed_messages(&chunks, "tEXt", Some("secret"), None, plain, &credentials);
+        assert_eq!(messages(found), [(1, "hidden".into())]);
+
+        assert!(typed_messages(&chunks, "ruSt", None, Some("eve"), plain, &credentials).is_empty());
+        assert!(typed_messages(&chunks, "abCd", None, None, plain, &credentials).is_empty());
+    }
+
+    #[test]
+    fn test_candidate_chunks() {
+        let chunks = testing_chunks();
+        let chunks = with_offsets(&chunks);
+        let candidates: Vec<(usize, usize, String)> = candidate_chunks(&chunks)
+            .into_iter()
+            .map(|(index, offset, chunk)| (index, offset, chunk.chunk_type().to_string()))
+            .collect();
+        assert_eq!(
+            candidates,
+            [
+                (0, 20, "ruSt".into()),
+                (1, 40, "ruSt".into()),
+                (2, 50, "ruSt".into()),
+                (0, 60, "prIv".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_messages() {
+        let chunks = testing_chunks();
+        let chunks = with_offsets(&chunks);
+        let found = auto_messages(&chunks, PayloadEncoding::default(), &no_credentials());
+
+        let summary: Vec<(usize, &str, Option<&str>, Option<&str>)> = found
+            .iter()
+            .map(|m| {
+                (
+                    m.index,
+                    m.chunk_type.as_str(),
+                    m.label.as_deref(),
+                    m.message.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0, "ruSt", Some("alice"), Some("one")),
+                (1, "ruSt", Some("bob"), Some("two")),
+                (2, "ruSt", None, Some("three")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_auto_messages_reports_encrypted_payloads() {
+        let payload = Payload::new(None, Encryption::Password, vec![0; 64]).unwrap();
+        let chunk = Chunk::new(ChunkType::from_str("ruSt").unwrap(), payload.as_bytes());
+        let found = auto_messages(&[(0, &chunk)], PayloadEncoding::default(), &no_credentials());
+        assert_eq!(found.len(), 1);
+        assert!(found[0].error.as_deref().unwrap().contains("--password"));
+    }
+
+    #[test]
+    fn test_message_summaries_include_legacy_messages() {
+        let chunks = testing_chunks();
+        let summaries = message_summaries(&with_offsets(&chunks));
+
+        let listed: Vec<(usize, Option<&str>, usize)> = summaries
+            .iter()
+            .map(|m| (m.index, m.label.as_deref(), m.size))
+            .collect();
+        assert_eq!(listed, [(0, Some("alice"), 3), (1, Some("bob"), 3), (2, None, 5)]);
+    }
+
+    #[test]
+    fn test_dry_run_report() {
+        let original = Png::from_chunks(testing_chunks()).as_bytes();
+        let mut png = Png::from_chunks(testing_chunks());
+        png.remove_at(6).unwrap();
+        png.remove_at(1).unwrap();
+        png.insert_chunk(1, text_chunk("Comment", "changed"));
+        png.insert_chunk(6, Chunk::new(ChunkType::from_str("neWc").unwrap(), vec![1]));
+
+        let report = dry_run_report(&original, &png, "out.png").unwrap();
+        assert!(report.starts_with("Dry run, nothing was written. 'out.png' would be"));
+        assert!(report.contains(&format!("(input is {} bytes)", original.len())));
+        assert!(report.contains("~ [1] -> [1] tEXt modified"));
+        assert!(report.contains("- [6] prIv removed"));
+        assert!(report.contains("+ [6] neWc added"));
+        assert!(report.ends_with("1 added, 1 removed, 1 modified, 0 reordered, 6 unchanged\n"));
+    }
+
+    #[test]
+    fn test_extract_and_carve_dry_run() {
+        let dir = temp_dir("dry-run");
+        let input = dir.join("in.png");
+        fs::write(&input, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+        let filepath = input.to_string_lossy().to_string();
+
+        let out = dir.join("chunk.bin");
+        extract(ExtractArgs {
+            filepath: filepath.clone(),
+            chunk_type: String::from("ruSt"),
+            index: 0,
+            out: out.to_string_lossy().to_string(),
+            raw: false,
+            dry_run: true,
+            write: WriteOptions::default(),
+        })
+        .unwrap();
+        assert!(!out.exists());
+
+        carve(CarveArgs {
+            filepath,
+            out_dir: dir.to_string_lossy().to_string(),
+            dry_run: true,
+            write: WriteOptions::default(),
+        })
+        .unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_keygen() {
+        let dir = temp_dir("keygen");
+        let path = dir.join("secret.key");
+        let args = KeygenArgs {
+            out_file: Some(path.to_string_lossy().to_string()),
+            write: WriteOptions::default(),
+        };
+        keygen(args).unwrap();
+
+        let identities = Identity::parse_file(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(identities.len(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Encodes "classified" in a tEXt chunk of a new file with the extra `options`, and
+    /// returns the messages `credentials` read back from it
+    fn text_chunk_round_trip(
+        name: &str,
+        options: &[&str],
+        recipients: &[Recipient],
+        passwords: &[String],
+        credentials: &Credentials,
+    ) -> Vec<(usize, String)> {
+        let dir = temp_dir(name);
+        let input = dir.join("input.png");
+        let output = dir.join("output.png");
+        fs::write(&input, Png::from_chunks(testing_chunks()).as_bytes()).unwrap();
+
+        let input_arg = input.to_string_lossy().to_string();
+        let output_arg = output.to_string_lossy().to_string();
+        let mut argv = vec!["encode", "-f", &input_arg, "-o", &output_arg, "-c", "tEXt"];
+        argv.extend(["-k", "Hidden", "-m", "classified", "-e", "base64"]);
+        argv.extend(options);
+        let args = EncodeArgs::try_parse_from(argv).unwrap();
+        let message = args.encoding.encode(args.message.as_bytes()).unwrap();
+        encode_file(&input, &args, &message, None, recipients, passwords).unwrap();
+
+        let bytes = fs::read(&output).unwrap();
+        assert!(!bytes.windows(10).any(|window| window == b"classified"));
+        let png = Png::try_from(bytes.as_slice()).unwrap();
+        let chunks = with_offsets(png.chunks());
+        let found = typed_messages(
+            &chunks,
+            "tEXt",
+            Some("Hidden"),
+            None,
+            PayloadEncoding::Base64,
+            credentials,
+        );
+        fs::remove_dir_all(dir).unwrap();
+        messages(found)
+    }
+
+    #[test]
+    fn test_encrypted_text_chunk_with_recipient() {
+        let identity = Identity::generate().unwrap();
+        let recipients = [identity.to_recipient()];
+        let credentials = Credentials {
+            identities: vec![identity],
+            password: None,
+            type_passphrase: None,
+        };
+        let found = text_chunk_round_trip("text-recipient", &[], &recipients, &[], &credentials);
+        assert_eq!(found, [(2, "classified".into())]);
+    }
+
+    #[test]
+    fn test_encrypted_text_chunk_with_password() {
+        let passwords = [String::from("hunter2")];
+        let credentials = Credentials {
+            identities: Vec::new(),
+            password: Some(String::from("hunter2")),
+            type_passphrase: None,
+        };
+        let found =
+            text_chunk_round_trip("text-password", &["-p"], &[], &passwords, &credentials);
+        assert_eq!(found, [(2, "classified".into())]);
+    }
+}
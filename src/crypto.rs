@@ -0,0 +1,456 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use argon2::Argon2;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Prefix of a public key, which messages are encrypted to.
+pub const RECIPIENT_PREFIX: &str = "pngme-pk-";
+
+/// Prefix of a secret key, which decrypts messages sent to its public key.
+pub const IDENTITY_PREFIX: &str = "PNGME-SECRET-KEY-";
+
+const WRAP_INFO: &[u8] = b"pngme-x25519-v1";
+const PAYLOAD_INFO: &[u8] = b"pngme-payload-v1";
+const FILE_KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+const STANZA_LENGTH: usize = 32 + FILE_KEY_LENGTH + TAG_LENGTH;
+const SALT_LENGTH: usize = 16;
+/// Password encrypted messages are padded to a multiple of this many bytes.
+const SLOT_PADDING: usize = 64;
+
+/// An X25519 public key that messages can be encrypted to.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+/// An X25519 secret key that decrypts messages encrypted to its `Recipient`.
+#[derive(Clone)]
+pub struct Identity(StaticSecret);
+
+impl Identity {
+    /// Generates a new identity from the operating system's random number generator.
+    pub fn generate() -> crate::Result<Self> {
+        Ok(Self(StaticSecret::from(random_bytes::<32>()?)))
+    }
+
+    pub fn to_recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+
+    /// Reads every identity in a key file written by `keygen`. Empty lines and
+    /// lines starting with `#` are skipped.
+    pub fn parse_file(text: &str) -> crate::Result<Vec<Self>> {
+        let identities: Vec<Self> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::from_str)
+            .collect::<crate::Result<_>>()?;
+        if identities.is_empty() {
+            return Err("The identity file holds no secret keys".into());
+        }
+        Ok(identities)
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let bytes = decode_key(s, RECIPIENT_PREFIX)?;
+        Ok(Self(PublicKey::from(bytes)))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> crate::Result<Self> {
+        let bytes = decode_key(s, IDENTITY_PREFIX)?;
+        Ok(Self(StaticSecret::from(bytes)))
+    }
+}
+
+impl Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            RECIPIENT_PREFIX,
+            URL_SAFE_NO_PAD.encode(self.0.as_bytes())
+        )
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            IDENTITY_PREFIX,
+            URL_SAFE_NO_PAD.encode(self.0.as_bytes())
+        )
+    }
+}
+
+impl fmt::Debug for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Identity({})", self.to_recipient())
+    }
+}
+
+impl fmt::Debug for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Recipient({})", self)
+    }
+}
+
+fn decode_key(s: &str, prefix: &str) -> crate::Result<[u8; 32]> {
+    let encoded = s
+        .trim()
+        .strip_prefix(prefix)
+        .ok_or_else(|| format!("Keys of this kind start with '{}'", prefix))?;
+    let bytes = URL_SAFE_NO_PAD.decode(encoded)?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("'{}' does not hold a 32 byte key", s.trim()).into())
+}
+
+/// Fills `bytes` from the operating system's secure random number generator.
+pub(crate) fn random_fill(bytes: &mut [u8]) -> crate::Result<()> {
+    getrandom::getrandom(bytes).map_err(|e| format!("No randomness available: {}", e).into())
+}
+
+pub(crate) fn random_bytes<const N: usize>() -> crate::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    random_fill(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// A uniformly random number below `bound`, which must be 1 to 256. Bytes from the
+/// top of the range that would favour small numbers are rejected and drawn again.
+pub(crate) fn random_below(bound: usize) -> crate::Result<usize> {
+    let zone = 256 - 256 % bound;
+    loop {
+        let byte = random_bytes::<1>()?[0] as usize;
+        if byte < zone {
+            return Ok(byte % bound);
+        }
+    }
+}
+
+fn derive_key(ikm: &[u8], salt: &[u8], info: &[u8]) -> Key {
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+/// Encrypts `plaintext` so that any one of `recipients` can decrypt it.
+///
+/// A random file key encrypts the data with ChaCha20-Poly1305. The file key is
+/// then wrapped once per recipient, age style, with a key derived from an
+/// ephemeral X25519 exchange:
+///
+/// ```text
+/// count (1 byte) | count x (ephemeral public key | wrapped file key) | nonce | ciphertext
+/// ```
+pub fn encrypt(plaintext: &[u8], recipients: &[Recipient]) -> crate::Result<Vec<u8>> {
+    if recipients.is_empty() || recipients.len() > u8::MAX as usize {
+        return Err(format!("Messages can be encrypted to 1 to {} recipients", u8::MAX).into());
+    }
+
+    let file_key = random_bytes::<FILE_KEY_LENGTH>()?;
+
+    let mut bytes = vec![recipients.len() as u8];
+    for recipient in recipients {
+        let ephemeral = StaticSecret::from(random_bytes::<32>()?);
+        let ephemeral_public = PublicKey::from(&ephemeral);
+        let shared = ephemeral.diffie_hellman(&recipient.0);
+
+        let salt = [
+            &ephemeral_public.as_bytes()[..],
+            &recipient.0.as_bytes()[..],
+        ]
+        .concat();
+        let wrap_key = derive_key(shared.as_bytes(), &salt, WRAP_INFO);
+        let wrapped = ChaCha20Poly1305::new(&wrap_key)
+            .encrypt(&Nonce::default(), file_key.as_slice())
+            .map_err(|_| "Could not wrap the file key")?;
+
+        bytes.extend(ephemeral_public.as_bytes());
+        bytes.extend(wrapped);
+    }
+
+    let nonce = random_bytes::<NONCE_LENGTH>()?;
+    let payload_key = derive_key(&file_key, &nonce, PAYLOAD_INFO);
+    let ciphertext = ChaCha20Poly1305::new(&payload_key)
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Could not encrypt the message")?;
+
+    bytes.extend(nonce);
+    bytes.extend(ciphertext);
+    Ok(bytes)
+}
+
+/// Decrypts data written by `encrypt` with whichever of `identities` it was
+/// encrypted to.
+pub fn decrypt(bytes: &[u8], identities: &[Identity]) -> crate::Result<Vec<u8>> {
+    let (&count, rest) = bytes
+        .split_first()
+        .ok_or("Encrypted message is truncated")?;
+    let stanzas_length = count as usize * STANZA_LENGTH;
+    if rest.len() < stanzas_length + NONCE_LENGTH + TAG_LENGTH {
+        return Err("Encrypted message is truncated".into());
+    }
+    let (stanzas, rest) = rest.split_at(stanzas_length);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+
+    let file_key = stanzas
+        .chunks(STANZA_LENGTH)
+        .find_map(|stanza| {
+            let (ephemeral_public, wrapped) = stanza.split_at(32);
+            let ephemeral_public: [u8; 32] = ephemeral_public.try_into().ok()?;
+            identities.iter().find_map(|identity| {
+                let recipient = identity.to_recipient();
+                let shared = identity
+                    .0
+                    .diffie_hellman(&PublicKey::from(ephemeral_public));
+                let salt = [&ephemeral_public[..], &recipient.0.as_bytes()[..]].concat();
+                let wrap_key = derive_key(shared.as_bytes(), &salt, WRAP_INFO);
+                ChaCha20Poly1305::new(&wrap_key)
+                    .decrypt(&Nonce::default(), wrapped)
+                    .ok()
+            })
+        })
+        .ok_or("The message was not encrypted to any of the given identities")?;
+
+    let payload_key = derive_key(&file_key, nonce, PAYLOAD_INFO);
+    ChaCha20Poly1305::new(&payload_key)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "The encrypted message is damaged".into())
+}
+
+/// Encrypts one or two messages, each under its own password, so that a password
+/// reveals only its own message.
+///
+/// There are always two slots of the same size, the messages are padded to the
+/// same length and a missing second message is replaced by random bytes, so the
+/// data looks the same whether it holds one message or two. The slots are stored
+/// in random order:
+///
+/// ```text
+/// salt (16 bytes) | 2 x (nonce | ChaCha20-Poly1305(length (4 bytes) | message | padding))
+/// ```
+pub fn encrypt_deniable(messages: &[(&[u8], &str)]) -> crate::Result<Vec<u8>> {
+    if messages.is_empty() || messages.len() > 2 {
+        return Err("Give one or two messages to encrypt with passwords".into());
+    }
+    if messages.len() == 2 && messages[0].1 == messages[1].1 {
+        return Err("The two messages need different passwords".into());
+    }
+
+    let salt = random_bytes::<SALT_LENGTH>()?;
+    let longest = messages.iter().map(|(message, _)| message.len()).max();
+    let padded_length = (4 + longest.unwrap_or_default()).next_multiple_of(SLOT_PADDING);
+
+    let mut slots = Vec::new();
+    for (message, password) in messages {
+        let mut plaintext = (message.len() as u32).to_be_bytes().to_vec();
+        plaintext.extend(*message);
+        plaintext.resize(padded_length, 0);
+
+        let nonce = random_bytes::<NONCE_LENGTH>()?;
+        let ciphertext = ChaCha20Poly1305::new(&password_key(password, &salt)?)
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| "Could not encrypt the message")?;
+
+        let mut slot = nonce.to_vec();
+        slot.extend(ciphertext);
+        slots.push(slot);
+    }
+    if slots.len() == 1 {
+        // Random bytes can't be told apart from a nonce and ciphertext
+        let mut filler = vec![0; slots[0].len()];
+        random_fill(&mut filler)?;
+        slots.push(filler);
+    }
+    if random_bytes::<1>()?[0] & 1 == 1 {
+        slots.swap(0, 1);
+    }
+
+    Ok([salt.to_vec(), slots.concat()].concat())
+}
+
+/// Decrypts the message `password` opens in data written by `encrypt_deniable`.
+pub fn decrypt_deniable(bytes: &[u8], password: &str) -> crate::Result<Vec<u8>> {
+    let slots = bytes
+        .get(SALT_LENGTH..)
+        .filter(|slots| !slots.is_empty() && slots.len() % 2 == 0)
+        .ok_or("Encrypted message is truncated")?;
+    let slot_length = slots.len() / 2;
+    if slot_length < NONCE_LENGTH + 4 + TAG_LENGTH {
+        return Err("Encrypted message is truncated".into());
+    }
+
+    let cipher = ChaCha20Poly1305::new(&password_key(password, &bytes[..SALT_LENGTH])?);
+    let plaintext = slots
+        .chunks(slot_length)
+        .find_map(|slot| {
+            let (nonce, ciphertext) = slot.split_at(NONCE_LENGTH);
+            cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()
+        })
+        .ok_or("The password does not open any message")?;
+
+    let length = u32::from_be_bytes(plaintext[..4].try_into()?) as usize;
+    plaintext
+        .get(4..4 + length)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| "The encrypted message is damaged".into())
+}
+
+/// Encrypts `plaintext`, here a whole payload, under a key derived from `passphrase`
+/// so that not even the payload magic can be seen without the passphrase:
+///
+/// ```text
+/// salt (16 bytes) | nonce | ChaCha20-Poly1305(plaintext)
+/// ```
+pub fn seal(plaintext: &[u8], passphrase: &str) -> crate::Result<Vec<u8>> {
+    let salt = random_bytes::<SALT_LENGTH>()?;
+    let nonce = random_bytes::<NONCE_LENGTH>()?;
+    let ciphertext = ChaCha20Poly1305::new(&password_key(passphrase, &salt)?)
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| "Could not encrypt the payload")?;
+    Ok([&salt[..], &nonce[..], &ciphertext].concat())
+}
+
+/// Decrypts data written by `seal` with the same passphrase.
+pub fn open(bytes: &[u8], passphrase: &str) -> crate::Result<Vec<u8>> {
+    if bytes.len() < SALT_LENGTH + NONCE_LENGTH + TAG_LENGTH {
+        return Err("Sealed payload is truncated".into());
+    }
+    let (salt, rest) = bytes.split_at(SALT_LENGTH);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+    ChaCha20Poly1305::new(&password_key(passphrase, salt)?)
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "The type passphrase does not open the chunk".into())
+}
+
+/// Derives a key from a password with Argon2id.
+fn password_key(password: &str, salt: &[u8]) -> crate::Result<Key> {
+    let mut key = Key::default();
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Could not derive a key from the password: {}", e))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_round_trip() {
+        let identity = Identity::generate().unwrap();
+        let recipient = identity.to_recipient();
+
+        let parsed_identity = Identity::from_str(&identity.to_string()).unwrap();
+        assert_eq!(parsed_identity.to_recipient(), recipient);
+        assert_eq!(
+            Recipient::from_str(&recipient.to_string()).unwrap(),
+            recipient
+        );
+
+        assert!(Recipient::from_str(&identity.to_string()).is_err());
+        assert!(Identity::from_str(&recipient.to_string()).is_err());
+        assert!(Recipient::from_str("pngme-pk-AAAA").is_err());
+    }
+
+    #[test]
+    fn test_parse_identity_file() {
+        let identity = Identity::generate().unwrap();
+        let file = format!(
+            "# public key: {}\n\n{}\n",
+            identity.to_recipient(),
+            identity
+        );
+        let identities = Identity::parse_file(&file).unwrap();
+        assert_eq!(identities.len(), 1);
+        assert!(Identity::parse_file("# nothing here\n").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_to_several_recipients() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let eve = Identity::generate().unwrap();
+
+        let ciphertext = encrypt(b"secret", &[alice.to_recipient(), bob.to_recipient()]).unwrap();
+        assert_eq!(ciphertext[0], 2);
+
+        assert_eq!(decrypt(&ciphertext, &[alice]).unwrap(), b"secret");
+        assert_eq!(
+            decrypt(&ciphertext, &[eve.clone(), bob]).unwrap(),
+            b"secret"
+        );
+        assert!(decrypt(&ciphertext, &[eve]).is_err());
+    }
+
+    #[test]
+    fn test_decrypt_damaged() {
+        let identity = Identity::generate().unwrap();
+        let mut ciphertext = encrypt(b"secret", &[identity.to_recipient()]).unwrap();
+        let last = ciphertext.len() - 1;
+        ciphertext[last] ^= 1;
+        assert!(decrypt(&ciphertext, std::slice::from_ref(&identity)).is_err());
+        assert!(decrypt(&ciphertext[..20], &[identity]).is_err());
+        assert!(encrypt(b"secret", &[]).is_err());
+    }
+
+    #[test]
+    fn test_deniable_two_messages() {
+        let data = encrypt_deniable(&[(b"real", "alpha"), (b"decoy message", "beta")]).unwrap();
+        assert_eq!(decrypt_deniable(&data, "alpha").unwrap(), b"real");
+        assert_eq!(decrypt_deniable(&data, "beta").unwrap(), b"decoy message");
+        assert!(decrypt_deniable(&data, "gamma").is_err());
+    }
+
+    #[test]
+    fn test_deniable_one_message_looks_the_same() {
+        let one = encrypt_deniable(&[(b"real", "alpha")]).unwrap();
+        let two = encrypt_deniable(&[(b"real", "alpha"), (b"other", "beta")]).unwrap();
+        assert_eq!(one.len(), two.len());
+        assert_eq!(decrypt_deniable(&one, "alpha").unwrap(), b"real");
+
+        assert!(encrypt_deniable(&[(b"a", "same"), (b"b", "same")]).is_err());
+        assert!(decrypt_deniable(&one[..20], "alpha").is_err());
+    }
+
+    #[test]
+    fn test_seal() {
+        let sealed = seal(b"PNGME payload", "passphrase").unwrap();
+        assert!(!sealed.windows(5).any(|window| window == b"PNGME"));
+        assert_eq!(open(&sealed, "passphrase").unwrap(), b"PNGME payload");
+        assert!(open(&sealed, "other").is_err());
+        assert!(open(&sealed[..20], "passphrase").is_err());
+    }
+
+    #[test]
+    fn test_random_below() {
+        let mut seen = [false; 9];
+        for _ in 0..1000 {
+            seen[random_below(9).unwrap()] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        assert_eq!(random_below(1).unwrap(), 0);
+        assert!(random_below(256).unwrap() < 256);
+    }
+}
@@ -0,0 +1,361 @@
+use crate::chunk::Chunk;
+use crate::image_data::decode_pixels;
+use crate::png::Png;
+use crate::report::Decoded;
+
+/// How a chunk changed between two files. Indexes are positions in each file's chunk list.
+pub enum ChunkChange<'a> {
+    Unchanged {
+        a: usize,
+    },
+    /// The same chunk moved relative to the chunks around it
+    Reordered {
+        a: usize,
+        b: usize,
+        chunk: &'a Chunk,
+    },
+    /// A chunk of the same type holds different data
+    Modified {
+        a: usize,
+        b: usize,
+        old: &'a Chunk,
+        new: &'a Chunk,
+    },
+    Removed {
+        a: usize,
+        chunk: &'a Chunk,
+    },
+    Added {
+        b: usize,
+        chunk: &'a Chunk,
+    },
+}
+
+/// Whether two files hold the same image once the `IDAT` data is decoded.
+pub enum PixelComparison {
+    Identical,
+    Different,
+    /// The pixels couldn't be compared, with the reason why
+    Unknown(String),
+}
+
+/// Identifies a chunk without copying it. The type and CRC tell almost all chunks
+/// apart, so the data is only compared when those match.
+#[derive(PartialEq)]
+struct ChunkKey<'a> {
+    chunk_type: [u8; 4],
+    crc: u32,
+    data: &'a [u8],
+}
+
+impl<'a> From<&'a Chunk> for ChunkKey<'a> {
+    fn from(chunk: &'a Chunk) -> Self {
+        Self {
+            chunk_type: chunk.chunk_type().bytes(),
+            crc: chunk.crc(),
+            data: chunk.chunk_data(),
+        }
+    }
+}
+
+/// The differences between two PNG files.
+pub struct PngDiff<'a> {
+    pub changes: Vec<ChunkChange<'a>>,
+    pub pixels: PixelComparison,
+}
+
+/// Compares the chunk lists of `a` and `b` and their decoded image data.
+///
+/// Chunks that are identical in both files are aligned first, keeping their
+/// order. Identical chunks left over were reordered, leftover chunks of the same
+/// type were modified, and anything else was added or removed.
+pub fn diff<'a>(a: &'a Png, b: &'a Png) -> PngDiff<'a> {
+    let a_chunks = a.chunks();
+    let b_chunks = b.chunks();
+    let a_keys: Vec<ChunkKey> = a_chunks.iter().map(ChunkKey::from).collect();
+    let b_keys: Vec<ChunkKey> = b_chunks.iter().map(ChunkKey::from).collect();
+
+    let mut a_matched: Vec<Option<usize>> = vec![None; a_chunks.len()];
+    let mut b_matched = vec![false; b_chunks.len()];
+    let mut changes = Vec::new();
+
+    for (i, j) in longest_common_subsequence(&a_keys, &b_keys) {
+        a_matched[i] = Some(j);
+        b_matched[j] = true;
+        changes.push(ChunkChange::Unchanged { a: i });
+    }
+
+    // Pair off what is left, first by identical bytes and then by chunk type
+    let same_bytes = |i: usize, j: usize| a_keys[i] == b_keys[j];
+    let same_type = |i: usize, j: usize| a_chunks[i].chunk_type() == b_chunks[j].chunk_type();
+    for (exact, matches) in [
+        (true, &same_bytes as &dyn Fn(usize, usize) -> bool),
+        (false, &same_type),
+    ] {
+        for i in 0..a_chunks.len() {
+            if a_matched[i].is_some() {
+                continue;
+            }
+            if let Some(j) = (0..b_chunks.len()).find(|&j| !b_matched[j] && matches(i, j)) {
+                a_matched[i] = Some(j);
+                b_matched[j] = true;
+                changes.push(if exact {
+                    ChunkChange::Reordered {
+                        a: i,
+                        b: j,
+                        chunk: &a_chunks[i],
+                    }
+                } else {
+                    ChunkChange::Modified {
+                        a: i,
+                        b: j,
+                        old: &a_chunks[i],
+                        new: &b_chunks[j],
+                    }
+                });
+            }
+        }
+    }
+
+    for (i, matched) in a_matched.iter().enumerate() {
+        if matched.is_none() {
+            changes.push(ChunkChange::Removed {
+                a: i,
+                chunk: &a_chunks[i],
+            });
+        }
+    }
+    for (j, matched) in b_matched.iter().enumerate() {
+        if !matched {
+            changes.push(ChunkChange::Added {
+                b: j,
+                chunk: &b_chunks[j],
+            });
+        }
+    }
+
+    changes.sort_by_key(|change| match change {
+        ChunkChange::Unchanged { a, .. }
+        | ChunkChange::Reordered { a, .. }
+        | ChunkChange::Modified { a, .. }
+        | ChunkChange::Removed { a, .. } => (*a, 0),
+        ChunkChange::Added { b, .. } => (*b, 1),
+    });
+
+    PngDiff {
+        changes,
+        pixels: compare_pixels(a, b),
+    }
+}
+
+/// Describes the decoded fields that differ between two chunks of the same known
+/// type, as `- old` and `+ new` lines.
+pub fn field_differences(a: &Png, b: &Png, old: &Chunk, new: &Chunk) -> Vec<String> {
+    let decode = |png: &Png, chunk: &Chunk| {
+        let color_type = png.image_header().map(|header| header.color_type);
+        match Decoded::from_chunk(chunk, color_type) {
+            Ok(Some(decoded)) => Some(decoded.to_string()),
+            _ => None,
+        }
+    };
+
+    let (Some(old), Some(new)) = (decode(a, old), decode(b, new)) else {
+        return Vec::new();
+    };
+
+    // Decoded chunks display one field per line between a header and a closing brace
+    let fields = |text: &str| -> Vec<String> {
+        let lines: Vec<&str> = text.lines().collect();
+        lines[1..lines.len().saturating_sub(1)]
+            .iter()
+            .map(|line| line.trim().to_string())
+            .collect()
+    };
+    let (old, new) = (fields(&old), fields(&new));
+
+    let removed = old
+        .iter()
+        .filter(|line| !new.contains(line))
+        .map(|line| format!("- {}", line));
+    let added = new
+        .iter()
+        .filter(|line| !old.contains(line))
+        .map(|line| format!("+ {}", line));
+    removed.chain(added).collect()
+}
+
+fn compare_pixels(a: &Png, b: &Png) -> PixelComparison {
+    if a.image_header() != b.image_header() {
+        return PixelComparison::Unknown(String::from("the IHDR chunks differ"));
+    }
+    match (decode_pixels(a), decode_pixels(b)) {
+        (Ok(a), Ok(b)) if a == b => PixelComparison::Identical,
+        (Ok(_), Ok(_)) => PixelComparison::Different,
+        (Err(e), _) | (_, Err(e)) => PixelComparison::Unknown(e.to_string()),
+    }
+}
+
+/// Returns the index pairs of a longest common subsequence of `a` and `b`.
+///
+/// Uses Hirschberg's algorithm, which needs memory linear in the length of `b`
+/// rather than a table of every pair, after matching the common prefix and suffix
+/// directly. Those are usually all but a few chunks, e.g. for an encode dry run.
+fn longest_common_subsequence<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let mut pairs = Vec::new();
+    hirschberg(a, b, (0, 0), &mut pairs);
+    pairs
+}
+
+fn hirschberg<T: PartialEq>(
+    a: &[T],
+    b: &[T],
+    offset: (usize, usize),
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    pairs.extend((0..prefix).map(|k| (offset.0 + k, offset.1 + k)));
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    let offset = (offset.0 + prefix, offset.1 + prefix);
+
+    let suffix = a.iter().rev().zip(b.iter().rev()).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[..a.len() - suffix], &b[..b.len() - suffix]);
+
+    if a.len() == 1 {
+        if let Some(j) = b.iter().position(|y| *y == a[0]) {
+            pairs.push((offset.0, offset.1 + j));
+        }
+    } else if !a.is_empty() && !b.is_empty() {
+        // Split `a` in half and `b` where the two halves' subsequences meet
+        let half = a.len() / 2;
+        let forward = lcs_lengths(&a[..half], b);
+        let a_back: Vec<&T> = a[half..].iter().rev().collect();
+        let b_back: Vec<&T> = b.iter().rev().collect();
+        let backward = lcs_lengths(&a_back, &b_back);
+        let split = (0..=b.len())
+            .max_by_key(|&k| forward[k] + backward[b.len() - k])
+            .unwrap_or_default();
+
+        hirschberg(&a[..half], &b[..split], offset, pairs);
+        hirschberg(&a[half..], &b[split..], (offset.0 + half, offset.1 + split), pairs);
+    }
+
+    let end = (offset.0 + a.len(), offset.1 + b.len());
+    pairs.extend((0..suffix).map(|k| (end.0 + k, end.1 + k)));
+}
+
+/// The length of a longest common subsequence of `a` and each prefix of `b`.
+fn lcs_lengths<T: PartialEq>(a: &[T], b: &[T]) -> Vec<usize> {
+    let mut row = vec![0; b.len() + 1];
+    for x in a {
+        let mut diagonal = 0;
+        for (j, y) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if x == y {
+                diagonal + 1
+            } else {
+                above.max(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn describe(diff: &PngDiff) -> Vec<String> {
+        diff.changes
+            .iter()
+            .map(|change| match change {
+                ChunkChange::Unchanged { a } => format!("= {}", a),
+                ChunkChange::Reordered { a, b, .. } => format!("r {} {}", a, b),
+                ChunkChange::Modified { a, b, .. } => format!("~ {} {}", a, b),
+                ChunkChange::Removed { a, .. } => format!("- {}", a),
+                ChunkChange::Added { b, .. } => format!("+ {}", b),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_chunks() {
+        let a = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("tEXt", b"Comment\0one"),
+            chunk("tIME", &[7, 232, 1, 1, 0, 0, 0]),
+            chunk("IEND", b""),
+        ]);
+        let b = Png::from_chunks(vec![
+            chunk("IHDR", b"header"),
+            chunk("tIME", &[7, 232, 1, 1, 0, 0, 0]),
+            chunk("gAMA", &[0, 1, 134, 160]),
+            chunk("ruSt", b"new"),
+            chunk("IEND", b""),
+        ]);
+
+        let diff = diff(&a, &b);
+        assert_eq!(
+            describe(&diff),
+            vec!["= 0", "~ 1 2", "- 2", "= 3", "+ 3", "= 4"]
+        );
+
+        let ChunkChange::Modified { old, new, .. } = diff.changes[1] else {
+            panic!("expected a modified chunk");
+        };
+        assert_eq!(
+            field_differences(&a, &b, old, new),
+            vec!["- Gamma: 0.45455", "+ Gamma: 1.00000"]
+        );
+    }
+
+    #[test]
+    fn test_diff_reordered() {
+        let a = Png::from_chunks(vec![
+            chunk("aAAa", b"1"),
+            chunk("bBBb", b"2"),
+            chunk("cCCc", b"3"),
+        ]);
+        let b = Png::from_chunks(vec![
+            chunk("cCCc", b"3"),
+            chunk("aAAa", b"1"),
+            chunk("bBBb", b"2"),
+        ]);
+        assert_eq!(describe(&diff(&a, &b)), vec!["= 0", "= 1", "r 2 0"]);
+    }
+
+    #[test]
+    fn test_pixels_unknown_without_header() {
+        let a = Png::from_chunks(vec![chunk("IEND", b"")]);
+        assert!(matches!(diff(&a, &a).pixels, PixelComparison::Unknown(_)));
+    }
+
+    #[test]
+    fn test_longest_common_subsequence() {
+        // Pseudo-random sequences over a small alphabet, checked against the full table
+        let sequence = |seed: u32, length: usize| -> Vec<u32> {
+            (0..length as u32)
+                .map(|i| (i.wrapping_mul(2654435761) ^ seed) % 5)
+                .collect()
+        };
+        for (a, b) in [(sequence(1, 40), sequence(2, 33)), (sequence(3, 7), sequence(4, 50))] {
+            let pairs = longest_common_subsequence(&a, &b);
+            assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+            assert!(pairs.iter().all(|&(i, j)| a[i] == b[j]));
+            assert_eq!(pairs.len(), lcs_lengths(&a, &b)[b.len()]);
+        }
+
+        // Thousands of IDAT chunks with one chunk inserted stay cheap
+        let a: Vec<u32> = (0..20_000).collect();
+        let mut b = a.clone();
+        b.insert(10_000, u32::MAX);
+        assert_eq!(longest_common_subsequence(&a, &b).len(), a.len());
+    }
+}
@@ -0,0 +1,63 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use clap::ValueEnum;
+
+/// How a payload is turned into text before it is stored in a chunk.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PayloadEncoding {
+    /// Store the message as-is
+    #[default]
+    Plain,
+    /// Standard base64 with padding
+    Base64,
+    /// Z85 flavour of base85
+    Base85,
+}
+
+impl PayloadEncoding {
+    pub fn encode(&self, payload: &[u8]) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            PayloadEncoding::Plain => Ok(String::from_utf8(payload.to_vec())?),
+            PayloadEncoding::Base64 => Ok(STANDARD.encode(payload)),
+            PayloadEncoding::Base85 => Ok(z85::encode(payload)),
+        }
+    }
+
+    pub fn decode(&self, text: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        match self {
+            PayloadEncoding::Plain => Ok(text.as_bytes().to_vec()),
+            PayloadEncoding::Base64 => Ok(STANDARD.decode(text.trim())?),
+            PayloadEncoding::Base85 => Ok(z85::decode(text.trim())?),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let payload = b"This is where your secret message will be!";
+        for encoding in [
+            PayloadEncoding::Plain,
+            PayloadEncoding::Base64,
+            PayloadEncoding::Base85,
+        ] {
+            let text = encoding.encode(payload).unwrap();
+            assert_eq!(encoding.decode(&text).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn test_base64() {
+        let text = PayloadEncoding::Base64.encode(b"pngme").unwrap();
+        assert_eq!(text, "cG5nbWU=");
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert!(PayloadEncoding::Base64.decode("not base64!").is_err());
+        assert!(PayloadEncoding::Plain.encode(&[0xff, 0xfe]).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::known_chunk::ImageHeader;
+use crate::png::Png;
+use crate::text_chunk::inflate;
+
+/// Inflates the `IDAT` data of `png` and undoes the scanline filters, returning the
+/// raw samples of each row. Interlaced images are returned pass by pass, so two
+/// images only compare equal if they use the same interlace method.
+pub fn decode_pixels(png: &Png) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let header = png
+        .image_header()
+        .ok_or("The file has no valid IHDR chunk")?;
+
+    let compressed: Vec<u8> = png
+        .chunks()
+        .iter()
+        .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        .flat_map(|chunk| chunk.chunk_data().to_vec())
+        .collect();
+    let filtered = inflate(&compressed, header.raw_data_size())?;
+
+    let mut pixels = Vec::new();
+    let mut position = 0;
+    for (width, height) in header.passes() {
+        position += unfilter_pass(&header, width, height, &filtered[position..], &mut pixels)?;
+    }
+
+    Ok(pixels)
+}
+
+/// Unfilters one pass of `width` by `height` pixels from the start of `data` into
+/// `pixels` and returns how many bytes of `data` it used.
+fn unfilter_pass(
+    header: &ImageHeader,
+    width: u32,
+    height: u32,
+    data: &[u8],
+    pixels: &mut Vec<u8>,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let bits_per_pixel = header.bit_depth as usize * header.color_type.channels() as usize;
+    // Filters look back at the corresponding byte of the previous pixel, or byte if smaller
+    let bytes_per_pixel = bits_per_pixel.div_ceil(8);
+    let row_size = (width as usize * bits_per_pixel).div_ceil(8);
+
+    let mut previous = vec![0u8; row_size];
+    for row in 0..height as usize {
+        let start = row * (row_size + 1);
+        let line = data
+            .get(start..start + row_size + 1)
+            .ok_or("Image data is shorter than the IHDR chunk describes")?;
+        let filter = line[0];
+        let mut current = line[1..].to_vec();
+
+        for i in 0..row_size {
+            let left = if i >= bytes_per_pixel {
+                current[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = previous[i];
+            let upper_left = if i >= bytes_per_pixel {
+                previous[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, upper_left),
+                _ => return Err(format!("Unknown filter type {} in row {}", filter, row).into()),
+            };
+            current[i] = current[i].wrapping_add(predictor);
+        }
+
+        pixels.extend(&current);
+        previous = current;
+    }
+
+    Ok(height as usize * (row_size + 1))
+}
+
+fn paeth(left: u8, up: u8, upper_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - upper_left as i16;
+    let distance_left = (estimate - left as i16).abs();
+    let distance_up = (estimate - up as i16).abs();
+    let distance_upper_left = (estimate - upper_left as i16).abs();
+
+    if distance_left <= distance_up && distance_left <= distance_upper_left {
+        left
+    } else if distance_up <= distance_upper_left {
+        up
+    } else {
+        upper_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::known_chunk::{ColorType, KnownChunk};
+    use crate::text_chunk::deflate;
+    use std::str::FromStr;
+
+    fn png_with_image_data(filtered: &[u8], interlace_method: u8) -> Png {
+        let header = KnownChunk::Header(ImageHeader {
+            width: 3,
+            height: 2,
+            bit_depth: 8,
+            color_type: ColorType::Grayscale,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        });
+        let idat = Chunk::new(
+            ChunkType::from_str("IDAT").unwrap(),
+            deflate(filtered).unwrap(),
+        );
+        Png::from_chunks(vec![header.to_chunk().unwrap(), idat])
+    }
+
+    #[test]
+    fn test_unfiltered_rows() {
+        let png = png_with_image_data(&[0, 1, 2, 3, 0, 4, 5, 6], 0);
+        assert_eq!(decode_pixels(&png).unwrap(), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_filters_give_same_pixels() {
+        // Sub filter on the first row, Up filter on the second
+        let sub_up = png_with_image_data(&[1, 1, 1, 1, 2, 3, 3, 3], 0);
+        // Paeth on the first row, Average on the second
+        let paeth_average = png_with_image_data(&[4, 1, 1, 1, 3, 4, 2, 2], 0);
+        assert_eq!(decode_pixels(&sub_up).unwrap(), vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(
+            decode_pixels(&paeth_average).unwrap(),
+            vec![1, 2, 3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn test_interlaced_passes() {
+        // 3x2 image: pass 1 is (0,0), pass 4 is (2,0), pass 6 is (1,0), pass 7 is row 1
+        let png = png_with_image_data(&[0, 1, 0, 3, 0, 2, 0, 4, 5, 6], 1);
+        assert_eq!(decode_pixels(&png).unwrap(), vec![1, 3, 2, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_short_image_data() {
+        let png = png_with_image_data(&[0, 1, 2, 3], 0);
+        assert!(decode_pixels(&png).is_err());
+    }
+}
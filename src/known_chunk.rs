@@ -0,0 +1,855 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::text_chunk::{
+    check_compression_method, check_keyword, deflate, from_latin1, inflate, split_at_null,
+    to_latin1, MAX_INFLATED_SIZE,
+};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Color type from the `IHDR` chunk, which decides how several other chunks are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ColorType {
+    Grayscale,
+    Rgb,
+    Indexed,
+    GrayscaleAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    pub fn value(&self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Rgb => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::Rgba => 6,
+        }
+    }
+
+    /// Number of samples in a single pixel.
+    pub fn channels(&self) -> u8 {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Rgb => 3,
+            ColorType::Rgba => 4,
+        }
+    }
+
+    fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            ColorType::Grayscale => &[1, 2, 4, 8, 16],
+            ColorType::Indexed => &[1, 2, 4, 8],
+            _ => &[8, 16],
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ColorType::Grayscale),
+            2 => Ok(ColorType::Rgb),
+            3 => Ok(ColorType::Indexed),
+            4 => Ok(ColorType::GrayscaleAlpha),
+            6 => Ok(ColorType::Rgba),
+            _ => Err(format!("Invalid color type {}", value).into()),
+        }
+    }
+}
+
+impl Display for ColorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorType::Grayscale => "Grayscale",
+            ColorType::Rgb => "RGB",
+            ColorType::Indexed => "Indexed",
+            ColorType::GrayscaleAlpha => "Grayscale + alpha",
+            ColorType::Rgba => "RGBA",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Decoded `IHDR` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ImageHeader {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: ColorType,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+/// Starting column, starting row, column step and row step of each Adam7 pass.
+const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+impl ImageHeader {
+    /// Width and height of each non-empty pass the image data is stored in, seven
+    /// Adam7 passes for interlaced images and the whole image otherwise.
+    pub fn passes(&self) -> Vec<(u32, u32)> {
+        if self.interlace_method != 1 {
+            return vec![(self.width, self.height)];
+        }
+        ADAM7_PASSES
+            .iter()
+            .map(|&(x, y, dx, dy)| {
+                (
+                    self.width.saturating_sub(x).div_ceil(dx),
+                    self.height.saturating_sub(y).div_ceil(dy),
+                )
+            })
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .collect()
+    }
+
+    /// Size in bytes of the filtered, uncompressed image data described by this header.
+    pub fn raw_data_size(&self) -> u64 {
+        let bits_per_pixel = self.bit_depth as u64 * self.color_type.channels() as u64;
+        self.passes()
+            .iter()
+            .map(|&(width, height)| {
+                let row_size = (width as u64 * bits_per_pixel).div_ceil(8);
+                // Every scanline starts with a filter type byte
+                height as u64 * (row_size + 1)
+            })
+            .sum()
+    }
+}
+
+/// Decoded `tRNS` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Transparency {
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    /// Alpha value for each palette entry
+    Palette(Vec<u8>),
+}
+
+/// Decoded `bKGD` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum Background {
+    Gray(u16),
+    Rgb(u16, u16, u16),
+    PaletteIndex(u8),
+}
+
+/// Decoded `cHRM` chunk. Values are stored as the spec does, multiplied by 100000.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Chromaticities {
+    pub white: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+/// A single entry of a `sPLT` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SuggestedPaletteEntry {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub alpha: u16,
+    pub frequency: u16,
+}
+
+/// Decoded `tIME` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LastModified {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+/// A typed view of a standard chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "value"))]
+pub enum KnownChunk {
+    Header(ImageHeader),
+    Palette(Vec<[u8; 3]>),
+    Transparency(Transparency),
+    /// Image gamma multiplied by 100000
+    Gamma(u32),
+    Chromaticities(Chromaticities),
+    /// Rendering intent, 0 to 3
+    StandardRgb(u8),
+    IccProfile {
+        name: String,
+        profile: Vec<u8>,
+    },
+    SignificantBits(Vec<u8>),
+    Background(Background),
+    Histogram(Vec<u16>),
+    PhysicalDimensions {
+        x: u32,
+        y: u32,
+        /// 0 for unknown, 1 for metres
+        unit: u8,
+    },
+    SuggestedPalette {
+        name: String,
+        sample_depth: u8,
+        entries: Vec<SuggestedPaletteEntry>,
+    },
+    Time(LastModified),
+    Exif(Vec<u8>),
+}
+
+impl KnownChunk {
+    /// Decodes `chunk`, returning `None` if its type isn't a known chunk type.
+    ///
+    /// The layout of `tRNS`, `sBIT` and `bKGD` depends on the image's color type.
+    /// When `color_type` is `None` it is guessed from the chunk's length.
+    pub fn from_chunk(chunk: &Chunk, color_type: Option<ColorType>) -> Result<Option<Self>, Error> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let data = chunk.chunk_data();
+
+        let known = match chunk_type.as_str() {
+            "IHDR" => {
+                expect_length(&chunk_type, data, 13)?;
+                let header = ImageHeader {
+                    width: read_u32(data, 0),
+                    height: read_u32(data, 4),
+                    bit_depth: data[8],
+                    color_type: ColorType::try_from(data[9])?,
+                    compression_method: data[10],
+                    filter_method: data[11],
+                    interlace_method: data[12],
+                };
+                check_header(&header)?;
+                KnownChunk::Header(header)
+            }
+            "PLTE" => {
+                if data.is_empty() || !data.len().is_multiple_of(3) || data.len() > 256 * 3 {
+                    return Err("PLTE chunk must hold between 1 and 256 RGB entries".into());
+                }
+                if matches!(
+                    color_type,
+                    Some(ColorType::Grayscale) | Some(ColorType::GrayscaleAlpha)
+                ) {
+                    return Err("PLTE chunk is not allowed in grayscale images".into());
+                }
+                KnownChunk::Palette(data.chunks(3).map(|c| [c[0], c[1], c[2]]).collect())
+            }
+            "tRNS" => {
+                let color_type = color_type.unwrap_or(match data.len() {
+                    2 => ColorType::Grayscale,
+                    6 => ColorType::Rgb,
+                    _ => ColorType::Indexed,
+                });
+                KnownChunk::Transparency(match color_type {
+                    ColorType::Grayscale => {
+                        expect_length(&chunk_type, data, 2)?;
+                        Transparency::Gray(read_u16(data, 0))
+                    }
+                    ColorType::Rgb => {
+                        expect_length(&chunk_type, data, 6)?;
+                        Transparency::Rgb(read_u16(data, 0), read_u16(data, 2), read_u16(data, 4))
+                    }
+                    ColorType::Indexed => {
+                        if data.len() > 256 {
+                            return Err("tRNS chunk has more than 256 entries".into());
+                        }
+                        Transparency::Palette(data.to_vec())
+                    }
+                    _ => {
+                        return Err(
+                            "tRNS chunk is not allowed in images with an alpha channel".into()
+                        )
+                    }
+                })
+            }
+            "gAMA" => {
+                expect_length(&chunk_type, data, 4)?;
+                let gamma = read_u32(data, 0);
+                if gamma == 0 {
+                    return Err("gAMA chunk can't have a gamma of zero".into());
+                }
+                KnownChunk::Gamma(gamma)
+            }
+            "cHRM" => {
+                expect_length(&chunk_type, data, 32)?;
+                let point = |i: usize| (read_u32(data, i * 8), read_u32(data, i * 8 + 4));
+                KnownChunk::Chromaticities(Chromaticities {
+                    white: point(0),
+                    red: point(1),
+                    green: point(2),
+                    blue: point(3),
+                })
+            }
+            "sRGB" => {
+                expect_length(&chunk_type, data, 1)?;
+                if data[0] > 3 {
+                    return Err(format!("Invalid sRGB rendering intent {}", data[0]).into());
+                }
+                KnownChunk::StandardRgb(data[0])
+            }
+            "iCCP" => {
+                let (name, rest) = split_at_null(data).ok_or("iCCP chunk is missing a name")?;
+                let name = from_latin1(name);
+                check_keyword(&name)?;
+                let (&method, profile) = rest
+                    .split_first()
+                    .ok_or("iCCP chunk is missing its compression method")?;
+                check_compression_method(method)?;
+                KnownChunk::IccProfile {
+                    name,
+                    profile: inflate(profile, MAX_INFLATED_SIZE)?,
+                }
+            }
+            "sBIT" => {
+                if let Some(color_type) = color_type {
+                    let expected = match color_type {
+                        ColorType::Indexed => 3,
+                        other => other.channels() as usize,
+                    };
+                    expect_length(&chunk_type, data, expected)?;
+                } else if data.is_empty() || data.len() > 4 {
+                    return Err("sBIT chunk must have between 1 and 4 bytes".into());
+                }
+                if data.contains(&0) {
+                    return Err("sBIT chunk can't have zero significant bits".into());
+                }
+                KnownChunk::SignificantBits(data.to_vec())
+            }
+            "bKGD" => {
+                let color_type = color_type.unwrap_or(match data.len() {
+                    1 => ColorType::Indexed,
+                    2 => ColorType::Grayscale,
+                    _ => ColorType::Rgb,
+                });
+                KnownChunk::Background(match color_type {
+                    ColorType::Indexed => {
+                        expect_length(&chunk_type, data, 1)?;
+                        Background::PaletteIndex(data[0])
+                    }
+                    ColorType::Grayscale | ColorType::GrayscaleAlpha => {
+                        expect_length(&chunk_type, data, 2)?;
+                        Background::Gray(read_u16(data, 0))
+                    }
+                    ColorType::Rgb | ColorType::Rgba => {
+                        expect_length(&chunk_type, data, 6)?;
+                        Background::Rgb(read_u16(data, 0), read_u16(data, 2), read_u16(data, 4))
+                    }
+                })
+            }
+            "hIST" => {
+                if data.is_empty() || !data.len().is_multiple_of(2) || data.len() > 256 * 2 {
+                    return Err("hIST chunk must hold between 1 and 256 frequencies".into());
+                }
+                KnownChunk::Histogram(
+                    (0..data.len())
+                        .step_by(2)
+                        .map(|i| read_u16(data, i))
+                        .collect(),
+                )
+            }
+            "pHYs" => {
+                expect_length(&chunk_type, data, 9)?;
+                if data[8] > 1 {
+                    return Err(format!("Invalid pHYs unit {}", data[8]).into());
+                }
+                KnownChunk::PhysicalDimensions {
+                    x: read_u32(data, 0),
+                    y: read_u32(data, 4),
+                    unit: data[8],
+                }
+            }
+            "sPLT" => {
+                let (name, rest) = split_at_null(data).ok_or("sPLT chunk is missing a name")?;
+                let name = from_latin1(name);
+                check_keyword(&name)?;
+                let (&sample_depth, entries) = rest
+                    .split_first()
+                    .ok_or("sPLT chunk is missing its sample depth")?;
+                let entry_size = match sample_depth {
+                    8 => 6,
+                    16 => 10,
+                    _ => return Err(format!("Invalid sPLT sample depth {}", sample_depth).into()),
+                };
+                if !entries.len().is_multiple_of(entry_size) {
+                    return Err("sPLT chunk has a partial palette entry".into());
+                }
+                let entries = entries
+                    .chunks(entry_size)
+                    .map(|e| {
+                        if sample_depth == 8 {
+                            SuggestedPaletteEntry {
+                                red: e[0] as u16,
+                                green: e[1] as u16,
+                                blue: e[2] as u16,
+                                alpha: e[3] as u16,
+                                frequency: read_u16(e, 4),
+                            }
+                        } else {
+                            SuggestedPaletteEntry {
+                                red: read_u16(e, 0),
+                                green: read_u16(e, 2),
+                                blue: read_u16(e, 4),
+                                alpha: read_u16(e, 6),
+                                frequency: read_u16(e, 8),
+                            }
+                        }
+                    })
+                    .collect();
+                KnownChunk::SuggestedPalette {
+                    name,
+                    sample_depth,
+                    entries,
+                }
+            }
+            "tIME" => {
+                expect_length(&chunk_type, data, 7)?;
+                let time = LastModified {
+                    year: read_u16(data, 0),
+                    month: data[2],
+                    day: data[3],
+                    hour: data[4],
+                    minute: data[5],
+                    second: data[6],
+                };
+                if !(1..=12).contains(&time.month)
+                    || !(1..=31).contains(&time.day)
+                    || time.hour > 23
+                    || time.minute > 59
+                    || time.second > 60
+                {
+                    return Err("tIME chunk holds an invalid date".into());
+                }
+                KnownChunk::Time(time)
+            }
+            "eXIf" => {
+                if !data.starts_with(b"MM\0*") && !data.starts_with(b"II*\0") {
+                    return Err("eXIf chunk doesn't start with a TIFF byte order mark".into());
+                }
+                KnownChunk::Exif(data.to_vec())
+            }
+            _ => return Ok(None),
+        };
+
+        Ok(Some(known))
+    }
+
+    /// Returns the chunk type this value is stored under.
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            KnownChunk::Header(_) => "IHDR",
+            KnownChunk::Palette(_) => "PLTE",
+            KnownChunk::Transparency(_) => "tRNS",
+            KnownChunk::Gamma(_) => "gAMA",
+            KnownChunk::Chromaticities(_) => "cHRM",
+            KnownChunk::StandardRgb(_) => "sRGB",
+            KnownChunk::IccProfile { .. } => "iCCP",
+            KnownChunk::SignificantBits(_) => "sBIT",
+            KnownChunk::Background(_) => "bKGD",
+            KnownChunk::Histogram(_) => "hIST",
+            KnownChunk::PhysicalDimensions { .. } => "pHYs",
+            KnownChunk::SuggestedPalette { .. } => "sPLT",
+            KnownChunk::Time(_) => "tIME",
+            KnownChunk::Exif(_) => "eXIf",
+        }
+    }
+
+    /// Serializes this value into a new `Chunk`.
+    pub fn to_chunk(&self) -> Result<Chunk, Error> {
+        let mut data: Vec<u8> = Vec::new();
+        match self {
+            KnownChunk::Header(header) => {
+                check_header(header)?;
+                data.extend(header.width.to_be_bytes());
+                data.extend(header.height.to_be_bytes());
+                data.extend([
+                    header.bit_depth,
+                    header.color_type.value(),
+                    header.compression_method,
+                    header.filter_method,
+                    header.interlace_method,
+                ]);
+            }
+            KnownChunk::Palette(entries) => entries.iter().for_each(|e| data.extend(e)),
+            KnownChunk::Transparency(Transparency::Gray(gray)) => data.extend(gray.to_be_bytes()),
+            KnownChunk::Transparency(Transparency::Rgb(r, g, b))
+            | KnownChunk::Background(Background::Rgb(r, g, b)) => {
+                [r, g, b].iter().for_each(|v| data.extend(v.to_be_bytes()))
+            }
+            KnownChunk::Transparency(Transparency::Palette(alphas)) => data.extend(alphas),
+            KnownChunk::Gamma(gamma) => data.extend(gamma.to_be_bytes()),
+            KnownChunk::Chromaticities(c) => {
+                [c.white, c.red, c.green, c.blue].iter().for_each(|(x, y)| {
+                    data.extend(x.to_be_bytes());
+                    data.extend(y.to_be_bytes());
+                })
+            }
+            KnownChunk::StandardRgb(intent) => data.push(*intent),
+            KnownChunk::IccProfile { name, profile } => {
+                data.extend(to_latin1(name)?);
+                data.extend([0, 0]);
+                data.extend(deflate(profile)?);
+            }
+            KnownChunk::SignificantBits(bits) => data.extend(bits),
+            KnownChunk::Background(Background::Gray(gray)) => data.extend(gray.to_be_bytes()),
+            KnownChunk::Background(Background::PaletteIndex(index)) => data.push(*index),
+            KnownChunk::Histogram(frequencies) => frequencies
+                .iter()
+                .for_each(|f| data.extend(f.to_be_bytes())),
+            KnownChunk::PhysicalDimensions { x, y, unit } => {
+                data.extend(x.to_be_bytes());
+                data.extend(y.to_be_bytes());
+                data.push(*unit);
+            }
+            KnownChunk::SuggestedPalette {
+                name,
+                sample_depth,
+                entries,
+            } => {
+                data.extend(to_latin1(name)?);
+                data.push(0);
+                data.push(*sample_depth);
+                for e in entries {
+                    if *sample_depth == 8 {
+                        data.extend([e.red as u8, e.green as u8, e.blue as u8, e.alpha as u8]);
+                    } else {
+                        [e.red, e.green, e.blue, e.alpha]
+                            .iter()
+                            .for_each(|v| data.extend(v.to_be_bytes()));
+                    }
+                    data.extend(e.frequency.to_be_bytes());
+                }
+            }
+            KnownChunk::Time(time) => {
+                data.extend(time.year.to_be_bytes());
+                data.extend([time.month, time.day, time.hour, time.minute, time.second]);
+            }
+            KnownChunk::Exif(exif) => data.extend(exif),
+        }
+
+        let chunk = Chunk::new(ChunkType::from_str(self.chunk_type())?, data);
+        // Round trip through the parser so the same spec constraints apply
+        KnownChunk::from_chunk(&chunk, None)?;
+        Ok(chunk)
+    }
+}
+
+impl TryFrom<&Chunk> for KnownChunk {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        KnownChunk::from_chunk(chunk, None)?.ok_or_else(|| {
+            format!(
+                "Chunk of type '{}' is not a known chunk",
+                chunk.chunk_type()
+            )
+            .into()
+        })
+    }
+}
+
+impl Display for KnownChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {{", self.chunk_type())?;
+        match self {
+            KnownChunk::Header(h) => {
+                writeln!(f, "  Dimensions: {}x{}", h.width, h.height)?;
+                writeln!(f, "  Bit depth: {}", h.bit_depth)?;
+                writeln!(f, "  Color type: {}", h.color_type)?;
+                writeln!(f, "  Interlaced: {}", h.interlace_method == 1)?;
+            }
+            KnownChunk::Palette(entries) => writeln!(f, "  Entries: {}", entries.len())?,
+            KnownChunk::Transparency(Transparency::Gray(gray)) => writeln!(f, "  Gray: {}", gray)?,
+            KnownChunk::Transparency(Transparency::Rgb(r, g, b)) => {
+                writeln!(f, "  RGB: {}, {}, {}", r, g, b)?
+            }
+            KnownChunk::Transparency(Transparency::Palette(alphas)) => {
+                writeln!(f, "  Palette alphas: {}", alphas.len())?
+            }
+            KnownChunk::Gamma(gamma) => writeln!(f, "  Gamma: {:.5}", *gamma as f64 / 100000.0)?,
+            KnownChunk::Chromaticities(c) => {
+                for (name, (x, y)) in [
+                    ("White", c.white),
+                    ("Red", c.red),
+                    ("Green", c.green),
+                    ("Blue", c.blue),
+                ] {
+                    writeln!(
+                        f,
+                        "  {}: ({:.5}, {:.5})",
+                        name,
+                        x as f64 / 100000.0,
+                        y as f64 / 100000.0
+                    )?;
+                }
+            }
+            KnownChunk::StandardRgb(intent) => {
+                let intent = match intent {
+                    0 => "Perceptual",
+                    1 => "Relative colorimetric",
+                    2 => "Saturation",
+                    _ => "Absolute colorimetric",
+                };
+                writeln!(f, "  Rendering intent: {}", intent)?
+            }
+            KnownChunk::IccProfile { name, profile } => {
+                writeln!(f, "  Name: {}", name)?;
+                writeln!(f, "  Profile: {} bytes", profile.len())?;
+            }
+            KnownChunk::SignificantBits(bits) => writeln!(f, "  Significant bits: {:?}", bits)?,
+            KnownChunk::Background(Background::Gray(gray)) => writeln!(f, "  Gray: {}", gray)?,
+            KnownChunk::Background(Background::Rgb(r, g, b)) => {
+                writeln!(f, "  RGB: {}, {}, {}", r, g, b)?
+            }
+            KnownChunk::Background(Background::PaletteIndex(index)) => {
+                writeln!(f, "  Palette index: {}", index)?
+            }
+            KnownChunk::Histogram(frequencies) => writeln!(f, "  Entries: {}", frequencies.len())?,
+            KnownChunk::PhysicalDimensions { x, y, unit } => {
+                let unit = if *unit == 1 { " per metre" } else { "" };
+                writeln!(f, "  Pixels: {} x {}{}", x, y, unit)?
+            }
+            KnownChunk::SuggestedPalette {
+                name,
+                sample_depth,
+                entries,
+            } => {
+                writeln!(f, "  Name: {}", name)?;
+                writeln!(f, "  Sample depth: {}", sample_depth)?;
+                writeln!(f, "  Entries: {}", entries.len())?;
+            }
+            KnownChunk::Time(t) => writeln!(
+                f,
+                "  Modified: {:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                t.year, t.month, t.day, t.hour, t.minute, t.second
+            )?,
+            KnownChunk::Exif(exif) => writeln!(f, "  Exif: {} bytes", exif.len())?,
+        }
+        write!(f, "}}")
+    }
+}
+
+fn check_header(header: &ImageHeader) -> Result<(), Error> {
+    if header.width == 0 || header.height == 0 {
+        return Err("IHDR chunk can't have a zero dimension".into());
+    }
+    if header.width > i32::MAX as u32 || header.height > i32::MAX as u32 {
+        return Err("IHDR chunk dimensions can't exceed 2^31 - 1".into());
+    }
+    if !header
+        .color_type
+        .allowed_bit_depths()
+        .contains(&header.bit_depth)
+    {
+        return Err(format!(
+            "Bit depth {} is not allowed for color type {}",
+            header.bit_depth, header.color_type
+        )
+        .into());
+    }
+    if header.compression_method != 0 || header.filter_method != 0 {
+        return Err("IHDR chunk has an unknown compression or filter method".into());
+    }
+    if header.interlace_method > 1 {
+        return Err(format!("Invalid interlace method {}", header.interlace_method).into());
+    }
+    Ok(())
+}
+
+fn expect_length(chunk_type: &str, data: &[u8], length: usize) -> Result<(), Error> {
+    if data.len() != length {
+        return Err(format!(
+            "{} chunk must have {} bytes of data, found {}",
+            chunk_type,
+            length,
+            data.len()
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_from_data(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_header() -> ImageHeader {
+        ImageHeader {
+            width: 4,
+            height: 2,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        }
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let known = KnownChunk::Header(testing_header());
+        let chunk = known.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_data().len(), 13);
+        assert_eq!(KnownChunk::try_from(&chunk).unwrap(), known);
+    }
+
+    #[test]
+    fn test_raw_data_size() {
+        let mut header = testing_header();
+        assert_eq!(header.raw_data_size(), 2 * (4 * 3 + 1));
+
+        header.color_type = ColorType::Grayscale;
+        header.bit_depth = 1;
+        assert_eq!(header.raw_data_size(), 2 * (1 + 1));
+    }
+
+    #[test]
+    fn test_raw_data_size_interlaced() {
+        let mut header = testing_header();
+        header.interlace_method = 1;
+        // A 4x2 image has Adam7 passes of 1x1, 1x1, 2x1 and 4x1 pixels
+        assert_eq!(header.passes(), vec![(1, 1), (1, 1), (2, 1), (4, 1)]);
+        assert_eq!(header.raw_data_size(), (3 + 1) + (3 + 1) + (6 + 1) + (12 + 1));
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        let mut header = testing_header();
+        header.bit_depth = 4;
+        assert!(KnownChunk::Header(header).to_chunk().is_err());
+
+        let mut header = testing_header();
+        header.width = 0;
+        assert!(KnownChunk::Header(header).to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let values = vec![
+            KnownChunk::Palette(vec![[0, 0, 0], [255, 255, 255]]),
+            KnownChunk::Transparency(Transparency::Rgb(1, 2, 3)),
+            KnownChunk::Gamma(45455),
+            KnownChunk::Chromaticities(Chromaticities {
+                white: (31270, 32900),
+                red: (64000, 33000),
+                green: (30000, 60000),
+                blue: (15000, 6000),
+            }),
+            KnownChunk::StandardRgb(0),
+            KnownChunk::IccProfile {
+                name: String::from("sRGB"),
+                profile: vec![1, 2, 3, 4],
+            },
+            KnownChunk::SignificantBits(vec![5, 6, 5]),
+            KnownChunk::Background(Background::Gray(128)),
+            KnownChunk::Histogram(vec![1, 2, 3]),
+            KnownChunk::PhysicalDimensions {
+                x: 2835,
+                y: 2835,
+                unit: 1,
+            },
+            KnownChunk::SuggestedPalette {
+                name: String::from("web"),
+                sample_depth: 16,
+                entries: vec![SuggestedPaletteEntry {
+                    red: 1,
+                    green: 2,
+                    blue: 3,
+                    alpha: 65535,
+                    frequency: 10,
+                }],
+            },
+            KnownChunk::Time(LastModified {
+                year: 2024,
+                month: 2,
+                day: 29,
+                hour: 12,
+                minute: 30,
+                second: 0,
+            }),
+            KnownChunk::Exif(b"MM\0*\0\0\0\x08".to_vec()),
+        ];
+
+        for known in values {
+            let chunk = known.to_chunk().unwrap();
+            assert_eq!(&chunk.chunk_type().to_string(), known.chunk_type());
+            assert_eq!(KnownChunk::try_from(&chunk).unwrap(), known);
+        }
+    }
+
+    #[test]
+    fn test_color_type_context() {
+        let chunk = chunk_from_data("tRNS", &[0, 7]);
+        let gray = KnownChunk::from_chunk(&chunk, Some(ColorType::Grayscale)).unwrap();
+        assert_eq!(gray, Some(KnownChunk::Transparency(Transparency::Gray(7))));
+
+        let palette = KnownChunk::from_chunk(&chunk, Some(ColorType::Indexed)).unwrap();
+        assert_eq!(
+            palette,
+            Some(KnownChunk::Transparency(Transparency::Palette(vec![0, 7])))
+        );
+
+        assert!(KnownChunk::from_chunk(&chunk, Some(ColorType::Rgba)).is_err());
+    }
+
+    #[test]
+    fn test_constraints() {
+        assert!(KnownChunk::try_from(&chunk_from_data("gAMA", &[0, 0, 0, 0])).is_err());
+        assert!(KnownChunk::try_from(&chunk_from_data("sRGB", &[4])).is_err());
+        assert!(KnownChunk::try_from(&chunk_from_data("PLTE", &[1, 2])).is_err());
+        assert!(KnownChunk::try_from(&chunk_from_data("pHYs", &[0; 8])).is_err());
+        assert!(KnownChunk::try_from(&chunk_from_data("tIME", &[7, 232, 13, 1, 0, 0, 0])).is_err());
+        assert!(KnownChunk::try_from(&chunk_from_data("eXIf", b"not exif")).is_err());
+        assert!(KnownChunk::try_from(&chunk_from_data("sPLT", b"web\0\x07")).is_err());
+    }
+
+    #[test]
+    fn test_unknown_chunk() {
+        let chunk = chunk_from_data("RuSt", b"data");
+        assert_eq!(KnownChunk::from_chunk(&chunk, None).unwrap(), None);
+        assert!(KnownChunk::try_from(&chunk).is_err());
+    }
+}
@@ -0,0 +1,22 @@
+pub mod args;
+pub mod atomic;
+pub mod batch;
+pub mod chunk;
+pub mod chunk_type;
+pub mod commands;
+pub mod crypto;
+pub mod diff;
+pub mod encoding;
+pub mod image_data;
+pub mod known_chunk;
+pub mod output;
+pub mod payload;
+pub mod png;
+pub mod report;
+pub mod scan;
+pub mod shamir;
+pub mod stdio;
+pub mod text_chunk;
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,30 +1,34 @@
 use clap::Parser;
 
-mod args;
-mod chunk;
-mod chunk_type;
-mod commands;
-mod png;
+use pngme::{args, commands};
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
-
-fn main() -> Result<()> {
+fn main() {
     let args = args::Args::parse();
-    match args.command {
-        args::PngMeArgs::Encode(encode_args) => {
-            let _ = commands::encode(encode_args);
-        }
-        args::PngMeArgs::Decode(decode_args) => {
-            let _ = commands::decode(decode_args);
-        }
-        args::PngMeArgs::Remove(remove_args) => {
-            let _ = commands::remove(remove_args);
-        }
-        args::PngMeArgs::Print(print_args) => {
-            let _ = commands::print_chunks(print_args);
-        }
+    if let Err(e) = args.validate() {
+        e.exit();
     }
+    let result = match args.command {
+        args::PngMeArgs::Encode(encode_args) => commands::encode(encode_args),
+        args::PngMeArgs::Decode(decode_args) => commands::decode(decode_args),
+        args::PngMeArgs::Remove(remove_args) => commands::remove(remove_args),
+        args::PngMeArgs::Print(print_args) => commands::print_chunks(print_args),
+        args::PngMeArgs::Dump(dump_args) => commands::dump(dump_args),
+        args::PngMeArgs::Extract(extract_args) => commands::extract(extract_args),
+        args::PngMeArgs::Inject(inject_args) => commands::inject(inject_args),
+        args::PngMeArgs::Strip(strip_args) => commands::strip(strip_args),
+        args::PngMeArgs::Normalize(normalize_args) => commands::normalize(normalize_args),
+        args::PngMeArgs::Repair(repair_args) => commands::repair(repair_args),
+        args::PngMeArgs::Carve(carve_args) => commands::carve(carve_args),
+        args::PngMeArgs::Trailer(trailer_args) => commands::trailer(trailer_args),
+        args::PngMeArgs::Diff(diff_args) => commands::diff(diff_args),
+        args::PngMeArgs::ListMessages(list_args) => commands::list_messages(list_args),
+        args::PngMeArgs::Keygen(keygen_args) => commands::keygen(keygen_args),
+        args::PngMeArgs::SplitEncode(split_args) => commands::split_encode(split_args),
+        args::PngMeArgs::CombineDecode(combine_args) => commands::combine_decode(combine_args),
+    };
 
-    Ok(())
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,166 @@
+use clap::ValueEnum;
+
+/// Output format shared by the commands that report on a file.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// JSON, for use by other tools (needs the `serde` feature)
+    Json,
+    /// YAML, for use by other tools (needs the `serde` feature)
+    Yaml,
+}
+
+/// Renders `value` in `format`, using its `Display` impl for text output.
+#[cfg(feature = "serde")]
+pub fn render<T>(value: &T, format: OutputFormat) -> crate::Result<String>
+where
+    T: std::fmt::Display + serde::Serialize,
+{
+    match format {
+        OutputFormat::Text => Ok(value.to_string()),
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(value)?),
+        OutputFormat::Yaml => Ok(serde_yaml::to_string(value)?),
+    }
+}
+
+/// Renders `value` in `format`, using its `Display` impl for text output.
+#[cfg(not(feature = "serde"))]
+pub fn render<T>(value: &T, format: OutputFormat) -> crate::Result<String>
+where
+    T: std::fmt::Display,
+{
+    match format {
+        OutputFormat::Text => Ok(value.to_string()),
+        _ => {
+            Err("pngme was built without the `serde` feature, only text output is available".into())
+        }
+    }
+}
+
+/// Combines the JSON or YAML each of several files was rendered to. JSON becomes
+/// a single array and YAML a stream of `---` separated documents, each holding
+/// `file` and either `result` or the `error` the file failed with.
+#[cfg(feature = "serde")]
+pub fn render_batch(
+    results: &[(String, Result<String, String>)],
+    format: OutputFormat,
+) -> crate::Result<String> {
+    use serde_json::{json, Value};
+
+    let mut documents = Vec::new();
+    for (file, result) in results {
+        documents.push(match (result, format) {
+            (Err(error), _) => json!({ "file": file, "error": error }),
+            (Ok(output), OutputFormat::Yaml) => {
+                let value: Value = serde_yaml::from_str(output)?;
+                json!({ "file": file, "result": value })
+            }
+            (Ok(output), _) => {
+                let value: Value = serde_json::from_str(output)?;
+                json!({ "file": file, "result": value })
+            }
+        });
+    }
+
+    match format {
+        OutputFormat::Yaml => {
+            let mut yaml = String::new();
+            for document in documents {
+                yaml.push_str("---\n");
+                yaml.push_str(&serde_yaml::to_string(&document)?);
+            }
+            Ok(yaml)
+        }
+        _ => Ok(format!("{}\n", serde_json::to_string_pretty(&documents)?)),
+    }
+}
+
+/// Combines the JSON or YAML each of several files was rendered to.
+#[cfg(not(feature = "serde"))]
+pub fn render_batch(
+    _results: &[(String, Result<String, String>)],
+    _format: OutputFormat,
+) -> crate::Result<String> {
+    Err("pngme was built without the `serde` feature, only text output is available".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::known_chunk::{Background, ColorType, ImageHeader, KnownChunk, Transparency};
+    use crate::png::Png;
+    use crate::report::PrintReport;
+    use crate::text_chunk::TextChunk;
+
+    fn testing_png() -> Png {
+        let header = ImageHeader {
+            width: 16,
+            height: 16,
+            bit_depth: 8,
+            color_type: ColorType::Rgb,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+        let chunks = [
+            KnownChunk::Header(header),
+            KnownChunk::Transparency(Transparency::Rgb(1, 2, 3)),
+            KnownChunk::Background(Background::Rgb(4, 5, 6)),
+        ];
+        let mut chunks: Vec<_> = chunks.iter().map(|c| c.to_chunk().unwrap()).collect();
+        let text = TextChunk::new("tEXt", String::from("Comment"), String::from("hi"));
+        chunks.push(text.unwrap().to_chunk().unwrap());
+        Png::from_chunks(chunks)
+    }
+
+    #[test]
+    fn test_render_text() {
+        let png = testing_png();
+        let text = render(&PrintReport::new("icon.png", 0, &png), OutputFormat::Text).unwrap();
+        assert!(text.starts_with("PNG icon.png"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_render_print_report_as_yaml() {
+        let png = testing_png();
+        let yaml = render(&PrintReport::new("icon.png", 0, &png), OutputFormat::Yaml).unwrap();
+        assert!(yaml.contains("type: Transparency"));
+        assert!(yaml.contains("type: Background"));
+        assert!(yaml.contains("keyword: Comment"));
+
+        let json = render(&PrintReport::new("icon.png", 0, &png), OutputFormat::Json).unwrap();
+        assert!(json.contains("\"type\": \"Rgb\""));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_render_batch() {
+        let results = vec![
+            (String::from("a.png"), Ok(String::from("{\"message\": \"hi\"}\n"))),
+            (String::from("b.png"), Err(String::from("Not a PNG file"))),
+        ];
+        let json = render_batch(&results, OutputFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value[0]["file"], "a.png");
+        assert_eq!(value[0]["result"]["message"], "hi");
+        assert_eq!(value[1]["error"], "Not a PNG file");
+
+        let results = vec![
+            (String::from("a.png"), Ok(String::from("message: hi\n"))),
+            (String::from("b.png"), Ok(String::from("message: bye\n"))),
+        ];
+        let yaml = render_batch(&results, OutputFormat::Yaml).unwrap();
+        assert_eq!(yaml.matches("---\n").count(), 2);
+        assert!(yaml.contains("message: bye"));
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn test_render_needs_serde() {
+        let png = testing_png();
+        assert!(render(&PrintReport::new("icon.png", 0, &png), OutputFormat::Json).is_err());
+    }
+}
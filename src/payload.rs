@@ -0,0 +1,305 @@
+use std::fmt::{self, Display};
+
+use crate::shamir::Share;
+
+/// The bytes every payload written by pngme starts with.
+pub const MAGIC: &[u8; 5] = b"PNGME";
+
+/// The payload format version written and read by this build.
+pub const VERSION: u8 = 1;
+
+/// What the body of a payload holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadKind {
+    Message,
+    /// A `shamir::Share` of a message split across several images
+    Share,
+}
+
+impl PayloadKind {
+    fn value(&self) -> u8 {
+        match self {
+            PayloadKind::Message => 0,
+            PayloadKind::Share => 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for PayloadKind {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(PayloadKind::Message),
+            1 => Ok(PayloadKind::Share),
+            _ => Err(format!("Unknown payload kind {}", value).into()),
+        }
+    }
+}
+
+/// How the body of a payload is encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encryption {
+    None,
+    /// Encrypted to X25519 recipients, see `crypto::encrypt`
+    Recipients,
+    /// Encrypted with one or two passwords, see `crypto::encrypt_deniable`
+    Password,
+}
+
+impl Encryption {
+    fn value(&self) -> u8 {
+        match self {
+            Encryption::None => 0,
+            Encryption::Recipients => 1,
+            Encryption::Password => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for Encryption {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> crate::Result<Self> {
+        match value {
+            0 => Ok(Encryption::None),
+            1 => Ok(Encryption::Recipients),
+            2 => Ok(Encryption::Password),
+            _ => Err(format!("Unknown payload encryption {}", value).into()),
+        }
+    }
+}
+
+/// The container pngme stores a message in when it isn't written to a text chunk.
+///
+/// ```text
+/// "PNGME" | version | kind | encryption | label length | label (UTF-8) | body
+/// ```
+///
+/// The label names the message, e.g. after its recipient, so one image can carry
+/// several messages that can be told apart. Only the body is ever encrypted, so the
+/// header and label can be read by anyone. Chunks written by older versions of
+/// pngme hold the body alone and are read as an unlabelled payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    label: Option<String>,
+    kind: PayloadKind,
+    encryption: Encryption,
+    body: Vec<u8>,
+}
+
+impl Payload {
+    /// Creates a message payload, checking that the label fits in the length byte.
+    pub fn new(
+        label: Option<String>,
+        encryption: Encryption,
+        body: Vec<u8>,
+    ) -> crate::Result<Self> {
+        Self::with_kind(label, PayloadKind::Message, encryption, body)
+    }
+
+    /// Creates a payload holding a `shamir::Share`.
+    pub fn new_share(label: Option<String>, share: &Share) -> crate::Result<Self> {
+        Self::with_kind(
+            label,
+            PayloadKind::Share,
+            Encryption::None,
+            share.as_bytes(),
+        )
+    }
+
+    fn with_kind(
+        label: Option<String>,
+        kind: PayloadKind,
+        encryption: Encryption,
+        body: Vec<u8>,
+    ) -> crate::Result<Self> {
+        if let Some(label) = &label {
+            if label.is_empty() || label.len() > u8::MAX as usize {
+                return Err(format!(
+                    "Labels must be 1 to {} bytes long, '{}' is {}",
+                    u8::MAX,
+                    label,
+                    label.len()
+                )
+                .into());
+            }
+        }
+        Ok(Self {
+            label,
+            kind,
+            encryption,
+            body,
+        })
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn kind(&self) -> PayloadKind {
+        self.kind
+    }
+
+    pub fn encryption(&self) -> Encryption {
+        self.encryption
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Returns true if `data` starts with the payload magic.
+    pub fn has_magic(data: &[u8]) -> bool {
+        data.starts_with(MAGIC)
+    }
+
+    /// Reads a payload from chunk data. Data without the magic is taken to be the
+    /// body of a payload written by an older version of pngme.
+    pub fn from_chunk_data(data: &[u8]) -> crate::Result<Self> {
+        if Self::has_magic(data) {
+            Self::try_from(data)
+        } else {
+            Ok(Self {
+                label: None,
+                kind: PayloadKind::Message,
+                encryption: Encryption::None,
+                body: data.to_vec(),
+            })
+        }
+    }
+
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let label = self.label.as_deref().unwrap_or_default().as_bytes();
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.kind.value());
+        bytes.push(self.encryption.value());
+        bytes.push(label.len() as u8);
+        bytes.extend(label);
+        bytes.extend(&self.body);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Payload {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> crate::Result<Self> {
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or("Data does not start with the pngme payload magic")?;
+
+        let (&version, rest) = rest.split_first().ok_or("Payload is truncated")?;
+        if version != VERSION {
+            return Err(format!("Unsupported payload version {}", version).into());
+        }
+        let (&kind, rest) = rest.split_first().ok_or("Payload is truncated")?;
+        let kind = PayloadKind::try_from(kind)?;
+        let (&encryption, rest) = rest.split_first().ok_or("Payload is truncated")?;
+        let encryption = Encryption::try_from(encryption)?;
+
+        let (&label_length, rest) = rest.split_first().ok_or("Payload is truncated")?;
+        let label_length = label_length as usize;
+        if rest.len() < label_length {
+            return Err("Payload label is truncated".into());
+        }
+        let (label, body) = rest.split_at(label_length);
+
+        let label = match label_length {
+            0 => None,
+            _ => Some(String::from_utf8(label.to_vec())?),
+        };
+        Ok(Self {
+            label,
+            kind,
+            encryption,
+            body: body.to_vec(),
+        })
+    }
+}
+
+impl Display for Payload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            PayloadKind::Message => "message",
+            PayloadKind::Share => "share",
+        };
+        match &self.label {
+            Some(label) => write!(
+                f,
+                "Payload '{}' ({}, {} bytes",
+                label,
+                kind,
+                self.body.len()
+            )?,
+            None => write!(f, "Unlabelled payload ({}, {} bytes", kind, self.body.len())?,
+        }
+        match self.encryption {
+            Encryption::None => write!(f, ")"),
+            Encryption::Recipients => write!(f, ", encrypted to recipients)"),
+            Encryption::Password => write!(f, ", encrypted with a password)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = Payload::new(
+            Some(String::from("alice")),
+            Encryption::Recipients,
+            b"hello".to_vec(),
+        )
+        .unwrap();
+        let bytes = payload.as_bytes();
+        assert!(Payload::has_magic(&bytes));
+        assert_eq!(&bytes[..10], b"PNGME\x01\x00\x01\x05a");
+
+        let parsed = Payload::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed, payload);
+        assert_eq!(parsed.label(), Some("alice"));
+        assert_eq!(parsed.encryption(), Encryption::Recipients);
+        assert_eq!(parsed.body(), b"hello");
+    }
+
+    #[test]
+    fn test_unlabelled_payload() {
+        let payload = Payload::new(None, Encryption::None, b"hello".to_vec()).unwrap();
+        let parsed = Payload::try_from(payload.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.label(), None);
+        assert_eq!(parsed.body(), b"hello");
+    }
+
+    #[test]
+    fn test_share_payload() {
+        let share = crate::shamir::split(b"hi", 2, 2).unwrap().remove(0);
+        let payload = Payload::new_share(None, &share).unwrap();
+        let parsed = Payload::try_from(payload.as_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.kind(), PayloadKind::Share);
+        assert_eq!(Share::try_from(parsed.body()).unwrap(), share);
+    }
+
+    #[test]
+    fn test_legacy_chunk_data() {
+        let payload = Payload::from_chunk_data(b"plain message").unwrap();
+        assert_eq!(payload.label(), None);
+        assert_eq!(payload.body(), b"plain message");
+    }
+
+    #[test]
+    fn test_invalid_payloads() {
+        assert!(Payload::new(Some(String::new()), Encryption::None, vec![]).is_err());
+        assert!(Payload::new(Some("x".repeat(256)), Encryption::None, vec![]).is_err());
+        assert!(Payload::try_from(&b"PNGME"[..]).is_err());
+        assert!(Payload::try_from(&b"PNGME\x02\x00\x00\x00"[..]).is_err());
+        assert!(Payload::try_from(&b"PNGME\x01\x07\x00\x00"[..]).is_err());
+        assert!(Payload::try_from(&b"PNGME\x01\x00\x07\x00"[..]).is_err());
+        assert!(Payload::try_from(&b"PNGME\x01\x00\x00\x05ab"[..]).is_err());
+        assert!(Payload::try_from(&b"RuSt"[..]).is_err());
+    }
+}
@@ -1,10 +1,16 @@
-use std::{fmt::Display, u32, usize};
+use std::fmt::Display;
+use std::str::FromStr;
 
 use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::known_chunk::{ImageHeader, KnownChunk};
+use crate::text_chunk::TextChunk;
 
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
+    trailer: Vec<u8>,
 }
 
 impl Display for Png {
@@ -21,24 +27,180 @@ impl Display for Png {
     }
 }
 
+/// Where a new chunk goes in a `Png`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkPosition {
+    /// Immediately before the `IEND` chunk, or at the end if there is none
+    BeforeIend,
+    /// Immediately after the `IHDR` chunk
+    AfterIhdr,
+    /// At this index in the chunk list
+    Index(usize),
+}
+
+impl FromStr for ChunkPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "before-iend" => Ok(ChunkPosition::BeforeIend),
+            "after-ihdr" => Ok(ChunkPosition::AfterIhdr),
+            _ => s
+                .strip_prefix("index:")
+                .and_then(|index| index.parse().ok())
+                .map(ChunkPosition::Index)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid position '{}', expected before-iend, after-ihdr or index:N",
+                        s
+                    )
+                }),
+        }
+    }
+}
+
+/// Which ancillary chunks `Png::strip` removes. Critical chunks are always kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StripPreset {
+    /// Remove every ancillary chunk
+    All,
+    /// Remove only chunks that can identify the author or device: tIME, text and eXIf
+    Privacy,
+    /// Remove every ancillary chunk except those needed to render colors correctly
+    KeepColor,
+}
+
+impl StripPreset {
+    const PRIVACY_CHUNK_TYPES: [&'static str; 5] = ["tIME", "tEXt", "zTXt", "iTXt", "eXIf"];
+    const COLOR_CHUNK_TYPES: [&'static str; 6] = ["tRNS", "gAMA", "cHRM", "sRGB", "iCCP", "cICP"];
+
+    /// Returns true if this preset removes chunks of type `chunk_type`.
+    pub fn removes(&self, chunk_type: &ChunkType) -> bool {
+        if chunk_type.is_critical() {
+            return false;
+        }
+        let chunk_type = chunk_type.to_string();
+        match self {
+            StripPreset::All => true,
+            StripPreset::Privacy => Self::PRIVACY_CHUNK_TYPES.contains(&chunk_type.as_str()),
+            StripPreset::KeepColor => !Self::COLOR_CHUNK_TYPES.contains(&chunk_type.as_str()),
+        }
+    }
+}
+
+/// Options for `Png::normalize`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Merge all `IDAT` data and resplit it into chunks of at most this many bytes
+    pub idat_size: Option<usize>,
+    /// Keep unknown unsafe-to-copy chunks even when critical chunks changed
+    pub keep_unsafe: bool,
+}
+
+/// What `Png::normalize` changed.
+#[derive(Default)]
+pub struct NormalizeReport {
+    /// True if any chunk moved
+    pub reordered: bool,
+    /// Number of `IDAT` chunks before and after normalizing
+    pub idat_chunks: (usize, usize),
+    /// True if resplitting changed the `IDAT` chunks, even if not their number
+    pub idat_resplit: bool,
+    /// Chunks that followed the first `IEND`
+    pub after_iend: Vec<Chunk>,
+    /// Raw trailing data that followed the chunks
+    pub trailer: Vec<u8>,
+    /// Repeated chunks of types that may only appear once
+    pub duplicates: Vec<Chunk>,
+    /// Unknown unsafe-to-copy chunks dropped because critical chunks changed
+    pub unsafe_to_copy: Vec<Chunk>,
+}
+
+impl NormalizeReport {
+    pub fn is_unchanged(&self) -> bool {
+        !self.reordered
+            && !self.idat_resplit
+            && self.after_iend.is_empty()
+            && self.trailer.is_empty()
+            && self.duplicates.is_empty()
+            && self.unsafe_to_copy.is_empty()
+    }
+}
+
+/// A problem found while parsing a `Png` with `Png::try_from_lenient`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseDiagnostic {
+    /// The stored CRC of a chunk doesn't match its type and data
+    CrcMismatch {
+        index: usize,
+        offset: usize,
+        chunk_type: String,
+        stored: u32,
+        calculated: u32,
+    },
+    /// A chunk type contains bytes that aren't ASCII letters
+    InvalidChunkType { index: usize, offset: usize },
+    /// The file ends in the middle of a chunk
+    TruncatedChunk { offset: usize, length: usize },
+    /// Bytes follow the `IEND` chunk
+    TrailingData { offset: usize, length: usize },
+    /// The file has no `IEND` chunk
+    MissingIend,
+}
+
+impl Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseDiagnostic::CrcMismatch {
+                index,
+                offset,
+                chunk_type,
+                stored,
+                calculated,
+            } => write!(
+                f,
+                "Chunk {} ({}) at offset {:#010x} has CRC {:#010x}, expected {:#010x}",
+                index, chunk_type, offset, stored, calculated
+            ),
+            ParseDiagnostic::InvalidChunkType { index, offset } => write!(
+                f,
+                "Chunk {} at offset {:#010x} has an invalid chunk type",
+                index, offset
+            ),
+            ParseDiagnostic::TruncatedChunk { offset, length } => write!(
+                f,
+                "{} byte(s) at offset {:#010x} don't form a complete chunk",
+                length, offset
+            ),
+            ParseDiagnostic::TrailingData { offset, length } => write!(
+                f,
+                "{} byte(s) of trailing data after IEND at offset {:#010x}",
+                length, offset
+            ),
+            ParseDiagnostic::MissingIend => write!(f, "The file has no IEND chunk"),
+        }
+    }
+}
+
 impl TryFrom<&[u8]> for Png {
     type Error = Box<dyn std::error::Error>;
 
     fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
         // Check if slice has at least enough elements to form the header of the file
         if value.len() < 8 {
-            return Err("Invalid chunks. Can't be zero".into());
+            Err("Invalid chunks. Can't be zero".into())
         } else {
             let header: &[u8] = &value[0..8];
             // Check if header corresponds to the correct standard header
             if header != Png::STANDARD_HEADER {
-                return Err("Invalid header. Can't form PNG".into());
+                Err("Invalid header. Can't form PNG".into())
             } else {
                 let mut formed_chunks: Vec<Chunk> = Vec::new();
                 //let remainder: &[u8] = &value[8..];
 
                 let mut current_position = 8;
-                while current_position < value.len() {
+                let mut found_iend = false;
+                while current_position < value.len() && !found_iend {
                     if value.len() - current_position < 4 {
                         return Err("Incomplete chunk length. Therefore can't form chunk".into());
                     }
@@ -70,6 +232,8 @@ impl TryFrom<&[u8]> for Png {
                         let chunk_bytes = &value[current_position..current_position + chunk_size];
 
                         let chunk = Chunk::try_from(chunk_bytes)?;
+                        // Anything after IEND is kept as raw trailing data
+                        found_iend = chunk.chunk_type().to_string() == "IEND";
                         formed_chunks.push(chunk);
 
                         // advance position
@@ -81,6 +245,7 @@ impl TryFrom<&[u8]> for Png {
                         .try_into()
                         .map_err(|_| "Error creating final header")?,
                     chunks: formed_chunks,
+                    trailer: value[current_position..].to_vec(),
                 })
             }
         }
@@ -98,13 +263,145 @@ impl Png {
         Self {
             header: Self::STANDARD_HEADER,
             chunks,
+            trailer: Vec::new(),
+        }
+    }
+
+    /// Parses a PNG without giving up on damaged chunks. Chunks with a bad CRC
+    /// are kept as they are, parsing stops at `IEND` or at the first incomplete
+    /// chunk, and every problem found is returned alongside the `Png`. Bytes
+    /// after `IEND` become the trailer, bytes of an incomplete chunk are dropped.
+    ///
+    /// Only a missing or invalid PNG signature is an error.
+    pub fn try_from_lenient(
+        value: &[u8],
+    ) -> Result<(Png, Vec<ParseDiagnostic>), Box<dyn std::error::Error>> {
+        if value.len() < 8 || value[0..8] != Png::STANDARD_HEADER {
+            return Err("Invalid header. Can't form PNG".into());
+        }
+
+        let mut chunks: Vec<Chunk> = Vec::new();
+        let mut diagnostics: Vec<ParseDiagnostic> = Vec::new();
+        let mut found_iend = false;
+
+        let mut current_position = 8;
+        while current_position < value.len() {
+            let remaining = value.len() - current_position;
+            let chunk_size = if remaining >= 12 {
+                let length_bytes = value[current_position..current_position + 4].try_into()?;
+                12 + u32::from_be_bytes(length_bytes) as usize
+            } else {
+                usize::MAX
+            };
+
+            if chunk_size > remaining {
+                diagnostics.push(ParseDiagnostic::TruncatedChunk {
+                    offset: current_position,
+                    length: remaining,
+                });
+                break;
+            }
+
+            let chunk =
+                Chunk::from_bytes_lenient(&value[current_position..current_position + chunk_size])?;
+            let index = chunks.len();
+            if !chunk
+                .chunk_type()
+                .chunk_type
+                .iter()
+                .all(|b| b.is_ascii_alphabetic())
+            {
+                diagnostics.push(ParseDiagnostic::InvalidChunkType {
+                    index,
+                    offset: current_position,
+                });
+            }
+            if !chunk.has_valid_crc() {
+                diagnostics.push(ParseDiagnostic::CrcMismatch {
+                    index,
+                    offset: current_position,
+                    chunk_type: chunk.chunk_type().to_string(),
+                    stored: chunk.crc(),
+                    calculated: chunk.calculated_crc(),
+                });
+            }
+
+            found_iend = chunk.chunk_type().to_string() == "IEND";
+            chunks.push(chunk);
+            current_position += chunk_size;
+
+            if found_iend {
+                if current_position < value.len() {
+                    diagnostics.push(ParseDiagnostic::TrailingData {
+                        offset: current_position,
+                        length: value.len() - current_position,
+                    });
+                }
+                break;
+            }
         }
+
+        if !found_iend {
+            diagnostics.push(ParseDiagnostic::MissingIend);
+        }
+
+        let mut png = Png::from_chunks(chunks);
+        if found_iend {
+            png.trailer = value[current_position..].to_vec();
+        }
+        Ok((png, diagnostics))
+    }
+
+    /// Recomputes every incorrect CRC, or only those of ancillary chunks when
+    /// `ancillary_only` is set. Returns the indexes of the chunks that changed.
+    pub fn repair_crcs(&mut self, ancillary_only: bool) -> Vec<usize> {
+        self.chunks
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, chunk)| !ancillary_only || !chunk.chunk_type().is_critical())
+            .filter_map(|(index, chunk)| chunk.fix_crc().then_some(index))
+            .collect()
     }
 
     pub fn append_chunk(&mut self, chunk: Chunk) {
         self.chunks.push(chunk);
     }
 
+    /// Inserts `chunk` at `index`, shifting all chunks after it.
+    pub fn insert_chunk(&mut self, index: usize, chunk: Chunk) {
+        self.chunks.insert(index, chunk);
+    }
+
+    /// Inserts `chunk` at `position` and returns the index it ended up at.
+    pub fn insert_chunk_at(
+        &mut self,
+        position: ChunkPosition,
+        chunk: Chunk,
+    ) -> Result<usize, Box<dyn std::error::Error>> {
+        let position_of = |chunk_type: &str| {
+            self.chunks
+                .iter()
+                .position(|c| c.chunk_type().to_string() == chunk_type)
+        };
+
+        let index = match position {
+            ChunkPosition::BeforeIend => position_of("IEND").unwrap_or(self.chunks.len()),
+            ChunkPosition::AfterIhdr => position_of("IHDR").ok_or("No IHDR chunk was found")? + 1,
+            ChunkPosition::Index(index) if index <= self.chunks.len() => index,
+            ChunkPosition::Index(index) => {
+                return Err(format!(
+                    "Index {} is past the end of the {} chunks",
+                    index,
+                    self.chunks.len()
+                )
+                .into())
+            }
+        };
+
+        self.chunks.insert(index, chunk);
+        Ok(index)
+    }
+
     pub fn remove_first_chunk(
         &mut self,
         chunk_type: &str,
@@ -120,12 +417,215 @@ impl Png {
         }
     }
 
+    /// Removes every chunk of type `chunk_type` and returns them in file order.
+    pub fn remove_all(&mut self, chunk_type: &str) -> Vec<Chunk> {
+        self.retain(|chunk| chunk.chunk_type().to_string() != chunk_type)
+    }
+
+    /// Removes the chunk at `index` in the chunk list.
+    pub fn remove_at(&mut self, index: usize) -> Result<Chunk, Box<dyn std::error::Error>> {
+        if index < self.chunks.len() {
+            Ok(self.chunks.remove(index))
+        } else {
+            Err(format!(
+                "Index {} is past the end of the {} chunks",
+                index,
+                self.chunks.len()
+            )
+            .into())
+        }
+    }
+
+    /// Keeps only the chunks for which `keep` returns true. Unlike `Vec::retain`
+    /// the removed chunks are returned, in file order.
+    pub fn retain<F>(&mut self, mut keep: F) -> Vec<Chunk>
+    where
+        F: FnMut(&Chunk) -> bool,
+    {
+        let (kept, removed) = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .partition(|chunk| keep(chunk));
+        self.chunks = kept;
+        removed
+    }
+
+    /// Removes the ancillary chunks selected by `preset`, and with `unsafe_to_copy`
+    /// also unknown chunks that are not safe to copy, except for any whose type is
+    /// listed in `keep`. Returns the removed chunks in file order.
+    pub fn strip(
+        &mut self,
+        preset: StripPreset,
+        keep: &[String],
+        unsafe_to_copy: bool,
+    ) -> Vec<Chunk> {
+        self.retain(|chunk| {
+            let chunk_type = chunk.chunk_type();
+            let removed =
+                preset.removes(chunk_type) || (unsafe_to_copy && is_unknown_unsafe(chunk_type));
+            !removed || keep.contains(&chunk_type.to_string())
+        })
+    }
+
+    /// Chunk types that may appear at most once in a file.
+    const SINGLETON_CHUNK_TYPES: [&'static str; 17] = [
+        "IHDR", "PLTE", "IEND", "tRNS", "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "cICP", "mDCV",
+        "cLLI", "bKGD", "hIST", "pHYs", "eXIf", "tIME",
+    ];
+
+    /// Rewrites this `Png` into canonical form: chunks and data after `IEND` and
+    /// repeated singleton chunks are dropped, chunks are sorted into the order the spec
+    /// recommends and `IDAT` data is optionally resplit.
+    ///
+    /// Unknown chunks keep their position relative to `PLTE` and `IDAT`. If any
+    /// critical chunk changed, unknown chunks that are not safe to copy are dropped
+    /// as the spec requires, unless `options.keep_unsafe` is set.
+    pub fn normalize(&mut self, options: NormalizeOptions) -> NormalizeReport {
+        let mut report = NormalizeReport::default();
+
+        let critical_before: Vec<Vec<u8>> = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type().is_critical())
+            .map(|chunk| chunk.as_bytes())
+            .collect();
+
+        if let Some(iend) = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "IEND")
+        {
+            report.after_iend = self.chunks.split_off(iend + 1);
+        }
+        report.trailer = self.set_trailer(Vec::new());
+
+        let mut seen: Vec<String> = Vec::new();
+        report.duplicates = self.retain(|chunk| {
+            let chunk_type = chunk.chunk_type().to_string();
+            if !Self::SINGLETON_CHUNK_TYPES.contains(&chunk_type.as_str()) {
+                return true;
+            }
+            if seen.contains(&chunk_type) {
+                return false;
+            }
+            seen.push(chunk_type);
+            true
+        });
+
+        // Unknown chunks stay in the segment they were found in
+        let mut seen_plte = false;
+        let mut seen_idat = false;
+        let mut ranked: Vec<(usize, Chunk)> = std::mem::take(&mut self.chunks)
+            .into_iter()
+            .map(|chunk| {
+                let chunk_type = chunk.chunk_type().to_string();
+                seen_plte |= chunk_type == "PLTE";
+                seen_idat |= chunk_type == "IDAT";
+                let rank = match chunk_type.as_str() {
+                    "IHDR" => 0,
+                    "cHRM" | "gAMA" | "iCCP" | "sBIT" | "sRGB" | "cICP" | "mDCV" | "cLLI" => 1,
+                    "PLTE" => 2,
+                    "tRNS" | "bKGD" | "hIST" => 3,
+                    "pHYs" | "sPLT" | "eXIf" | "tIME" | "tEXt" | "zTXt" | "iTXt" => 4,
+                    "IDAT" => 5,
+                    "IEND" => 7,
+                    _ if seen_idat => 6,
+                    _ if seen_plte => 4,
+                    _ => 1,
+                };
+                (rank, chunk)
+            })
+            .collect();
+        let ranks_before: Vec<usize> = ranked.iter().map(|(rank, _)| *rank).collect();
+        ranked.sort_by_key(|(rank, _)| *rank);
+        report.reordered = ranked.iter().map(|(rank, _)| *rank).ne(ranks_before);
+        self.chunks = ranked.into_iter().map(|(_, chunk)| chunk).collect();
+
+        let idat_bytes = |chunks: &[Chunk]| -> Vec<Vec<u8>> {
+            chunks
+                .iter()
+                .filter(|chunk| chunk.chunk_type().to_string() == "IDAT")
+                .map(|chunk| chunk.as_bytes())
+                .collect()
+        };
+        let idat_before = idat_bytes(&self.chunks);
+        if let Some(idat_size) = options.idat_size.filter(|size| *size > 0) {
+            self.resplit_idat(idat_size);
+        }
+        let idat_after = idat_bytes(&self.chunks);
+        report.idat_chunks = (idat_before.len(), idat_after.len());
+        report.idat_resplit = idat_before != idat_after;
+
+        let critical_after: Vec<Vec<u8>> = self
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.chunk_type().is_critical())
+            .map(|chunk| chunk.as_bytes())
+            .collect();
+        if critical_before != critical_after && !options.keep_unsafe {
+            report.unsafe_to_copy = self.retain(|chunk| !is_unknown_unsafe(chunk.chunk_type()));
+        }
+
+        report
+    }
+
+    /// Replaces all `IDAT` chunks with chunks of at most `idat_size` bytes holding
+    /// the same data, at the position of the first one.
+    fn resplit_idat(&mut self, idat_size: usize) {
+        let Some(first) = self
+            .chunks
+            .iter()
+            .position(|chunk| chunk.chunk_type().to_string() == "IDAT")
+        else {
+            return;
+        };
+
+        let data: Vec<u8> = self
+            .remove_all("IDAT")
+            .iter()
+            .flat_map(|chunk| chunk.chunk_data().to_vec())
+            .collect();
+
+        let resplit: Vec<Chunk> = data
+            .chunks(idat_size)
+            .map(|part| {
+                let chunk_type = ChunkType {
+                    chunk_type: b"IDAT".to_vec(),
+                };
+                Chunk::new(chunk_type, part.to_vec())
+            })
+            .collect();
+        self.chunks.splice(first..first, resplit);
+    }
+
     pub fn header(&self) -> &[u8; 8] {
         &self.header
     }
 
     pub fn chunks(&self) -> &[Chunk] {
-        &self.chunks.as_slice()
+        self.chunks.as_slice()
+    }
+
+    /// Returns the `index`th chunk of type `chunk_type` along with its byte offset
+    /// from the start of the file.
+    pub fn nth_chunk_by_type(&self, chunk_type: &str, index: usize) -> Option<(&Chunk, usize)> {
+        self.chunks
+            .iter()
+            .zip(self.chunk_offsets())
+            .filter(|(chunk, _)| chunk.chunk_type().to_string() == chunk_type)
+            .nth(index)
+    }
+
+    /// Returns the byte offset of each chunk from the start of the file.
+    pub fn chunk_offsets(&self) -> Vec<usize> {
+        let mut offset = self.header.len();
+        self.chunks
+            .iter()
+            .map(|chunk| {
+                let current = offset;
+                offset += 12 + chunk.length() as usize;
+                current
+            })
+            .collect()
     }
 
     /// Searches for a `Chunk` with the specified `chunk_type` and returns the first
@@ -136,19 +636,55 @@ impl Png {
             .find(|chunk| chunk.chunk_type().to_string() == chunk_type)
     }
 
+    /// Decodes the `IHDR` chunk, if this `Png` has a valid one.
+    pub fn image_header(&self) -> Option<ImageHeader> {
+        match self.chunk_by_type("IHDR")?.known(None) {
+            Ok(Some(KnownChunk::Header(header))) => Some(header),
+            _ => None,
+        }
+    }
+
+    /// Decodes every `tEXt`, `zTXt` and `iTXt` chunk in this `Png`, skipping any
+    /// that are malformed.
+    pub fn text_chunks(&self) -> Vec<TextChunk> {
+        self.chunks
+            .iter()
+            .filter(|chunk| TextChunk::is_text_chunk_type(&chunk.chunk_type().to_string()))
+            .filter_map(|chunk| TextChunk::try_from(chunk).ok())
+            .collect()
+    }
+
+    /// Returns the raw bytes that followed the `IEND` chunk when this `Png` was parsed.
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    /// Replaces the bytes written after the last chunk and returns the old ones.
+    pub fn set_trailer(&mut self, trailer: Vec<u8>) -> Vec<u8> {
+        std::mem::replace(&mut self.trailer, trailer)
+    }
+
     /// Returns this `Png` as a byte sequence.
-    /// These bytes will contain the header followed by the bytes of all of the chunks.
+    /// These bytes will contain the header followed by the bytes of all of the chunks
+    /// and then any trailing data.
     pub fn as_bytes(&self) -> Vec<u8> {
         let mut bytes = self.header().to_vec();
 
         self.chunks.iter().for_each(|chunk| {
             bytes.extend(chunk.as_bytes());
         });
+        bytes.extend(&self.trailer);
 
         bytes
     }
 }
 
+/// Returns true for chunks pngme doesn't know that are not safe to copy. The spec
+/// says these depend on the critical chunks, so editors must drop them once those change.
+fn is_unknown_unsafe(chunk_type: &ChunkType) -> bool {
+    !chunk_type.is_critical() && !chunk_type.is_standard() && !chunk_type.is_safe_to_copy()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -272,6 +808,111 @@ mod tests {
         assert_eq!(&chunk.data_as_string().unwrap(), "Message");
     }
 
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = testing_png();
+        png.insert_chunk(1, chunk_from_strings("TeSt", "Message").unwrap());
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "TeSt");
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_image_header() {
+        let mut png = testing_png();
+        assert!(png.image_header().is_none());
+
+        let ihdr = [0, 0, 0, 4, 0, 0, 0, 2, 8, 6, 0, 0, 0];
+        let chunk = Chunk::new(ChunkType::try_from(*b"IHDR").unwrap(), ihdr.to_vec());
+        png.insert_chunk(0, chunk);
+        let header = png.image_header().unwrap();
+        assert_eq!((header.width, header.height), (4, 2));
+    }
+
+    #[test]
+    fn test_chunk_offsets() {
+        let png = testing_png();
+        let offsets = png.chunk_offsets();
+        assert_eq!(offsets, vec![8, 8 + 12 + 20, 8 + 12 + 20 + 12 + 18]);
+    }
+
+    #[test]
+    fn test_nth_chunk_by_type() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "I am the second FrSt").unwrap());
+
+        let (chunk, offset) = png.nth_chunk_by_type("FrSt", 1).unwrap();
+        assert_eq!(&chunk.data_as_string().unwrap(), "I am the second FrSt");
+        assert_eq!(offset, png.chunk_offsets()[3]);
+        assert!(png.nth_chunk_by_type("FrSt", 2).is_none());
+    }
+
+    #[test]
+    fn test_chunk_position_from_str() {
+        assert_eq!(
+            ChunkPosition::from_str("before-iend").unwrap(),
+            ChunkPosition::BeforeIend
+        );
+        assert_eq!(
+            ChunkPosition::from_str("after-ihdr").unwrap(),
+            ChunkPosition::AfterIhdr
+        );
+        assert_eq!(
+            ChunkPosition::from_str("index:2").unwrap(),
+            ChunkPosition::Index(2)
+        );
+        assert!(ChunkPosition::from_str("index:two").is_err());
+        assert!(ChunkPosition::from_str("middle").is_err());
+    }
+
+    #[test]
+    fn test_insert_chunk_at() {
+        let mut png = testing_png();
+        png.insert_chunk(0, chunk_from_strings("IHDR", "header").unwrap());
+        png.append_chunk(chunk_from_strings("IEND", "").unwrap());
+
+        let index = png
+            .insert_chunk_at(
+                ChunkPosition::BeforeIend,
+                chunk_from_strings("TeSt", "1").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(index, 4);
+        assert_eq!(&png.chunks()[5].chunk_type().to_string(), "IEND");
+
+        let index = png
+            .insert_chunk_at(
+                ChunkPosition::AfterIhdr,
+                chunk_from_strings("TeSt", "2").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(index, 1);
+
+        let index = png
+            .insert_chunk_at(
+                ChunkPosition::Index(7),
+                chunk_from_strings("TeSt", "3").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(index, 7);
+
+        let result = png.insert_chunk_at(
+            ChunkPosition::Index(9),
+            chunk_from_strings("TeSt", "4").unwrap(),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_text_chunks() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0Hello").unwrap());
+        png.append_chunk(chunk_from_strings("tEXt", "Missing separator").unwrap());
+        let texts = png.text_chunks();
+        assert_eq!(texts.len(), 1);
+        assert_eq!(texts[0].keyword(), "Comment");
+        assert_eq!(texts[0].text(), "Hello");
+    }
+
     #[test]
     fn test_remove_first_chunk() {
         let mut png = testing_png();
@@ -281,6 +922,258 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_remove_all() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("FrSt", "Another first").unwrap());
+        let removed = png.remove_all("FrSt");
+        assert_eq!(removed.len(), 2);
+        assert_eq!(&removed[1].data_as_string().unwrap(), "Another first");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_all("FrSt").is_empty());
+    }
+
+    #[test]
+    fn test_remove_at() {
+        let mut png = testing_png();
+        let removed = png.remove_at(1).unwrap();
+        assert_eq!(&removed.chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+        assert!(png.remove_at(2).is_err());
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut png = testing_png();
+        let removed = png.retain(|chunk| chunk.chunk_type().is_critical());
+        assert_eq!(removed.len(), 1);
+        assert_eq!(&removed[0].chunk_type().to_string(), "miDl");
+        assert_eq!(png.chunks().len(), 2);
+    }
+
+    #[test]
+    fn test_strip() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("gAMA", "gamma").unwrap());
+        png.append_chunk(chunk_from_strings("tEXt", "Comment\0Hello").unwrap());
+        png.append_chunk(chunk_from_strings("tIME", "time").unwrap());
+
+        let removed = png.strip(StripPreset::KeepColor, &[String::from("tIME")], false);
+        let removed: Vec<String> = removed.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(removed, vec!["miDl", "tEXt"]);
+        assert_eq!(png.chunks().len(), 4);
+    }
+
+    #[test]
+    fn test_strip_unsafe_to_copy() {
+        let mut png = testing_png();
+        png.append_chunk(chunk_from_strings("tIME", "time").unwrap());
+        png.append_chunk(chunk_from_strings("ruST", "unsafe").unwrap());
+        png.append_chunk(chunk_from_strings("ruSt", "safe").unwrap());
+
+        assert_eq!(png.strip(StripPreset::Privacy, &[], false).len(), 1);
+        let removed = png.strip(StripPreset::Privacy, &[], true);
+        let removed: Vec<String> = removed.iter().map(|c| c.chunk_type().to_string()).collect();
+        assert_eq!(removed, vec!["ruST"]);
+
+        png.append_chunk(chunk_from_strings("ruST", "unsafe").unwrap());
+        assert!(png
+            .strip(StripPreset::Privacy, &[String::from("ruST")], true)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_strip_presets() {
+        let critical = ChunkType::from_str("IHDR").unwrap();
+        let text = ChunkType::from_str("tEXt").unwrap();
+        let gamma = ChunkType::from_str("gAMA").unwrap();
+
+        for preset in [
+            StripPreset::All,
+            StripPreset::Privacy,
+            StripPreset::KeepColor,
+        ] {
+            assert!(!preset.removes(&critical));
+            assert!(preset.removes(&text));
+        }
+        assert!(StripPreset::All.removes(&gamma));
+        assert!(!StripPreset::Privacy.removes(&gamma));
+        assert!(!StripPreset::KeepColor.removes(&gamma));
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "abc").unwrap(),
+            chunk_from_strings("gAMA", "gamma").unwrap(),
+            chunk_from_strings("IDAT", "defg").unwrap(),
+            chunk_from_strings("gAMA", "gamma again").unwrap(),
+            chunk_from_strings("ruST", "unsafe").unwrap(),
+            chunk_from_strings("ruSt", "safe").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+            chunk_from_strings("TrAl", "trailing").unwrap(),
+        ]);
+
+        let report = png.normalize(NormalizeOptions {
+            idat_size: Some(5),
+            keep_unsafe: false,
+        });
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "IDAT", "IDAT", "ruSt", "IEND"]);
+        assert_eq!(&png.chunks()[2].data_as_string().unwrap(), "abcde");
+        assert_eq!(&png.chunks()[3].data_as_string().unwrap(), "fg");
+
+        assert!(report.reordered);
+        assert_eq!(report.idat_chunks, (2, 2));
+        assert_eq!(report.after_iend.len(), 1);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(report.unsafe_to_copy.len(), 1);
+    }
+
+    #[test]
+    fn test_normalize_keeps_unsafe_when_critical_unchanged() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "abc").unwrap(),
+            chunk_from_strings("tEXt", "Comment\0hi").unwrap(),
+            chunk_from_strings("ruST", "unsafe").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+
+        let report = png.normalize(NormalizeOptions::default());
+        assert!(report.reordered);
+        assert!(report.unsafe_to_copy.is_empty());
+        assert_eq!(&png.chunks()[1].chunk_type().to_string(), "tEXt");
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "ruST");
+
+        let report = png.normalize(NormalizeOptions::default());
+        assert!(report.is_unchanged());
+    }
+
+    #[test]
+    fn test_normalize_resplit_with_same_idat_count() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("IDAT", "abc").unwrap(),
+            chunk_from_strings("IDAT", "defgh").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let options = NormalizeOptions {
+            idat_size: Some(4),
+            keep_unsafe: true,
+        };
+
+        let report = png.normalize(options);
+        assert_eq!(report.idat_chunks, (2, 2));
+        assert!(report.idat_resplit);
+        assert!(!report.is_unchanged());
+        assert_eq!(&png.chunks()[1].data_as_string().unwrap(), "abcd");
+
+        assert!(png.normalize(options).is_unchanged());
+    }
+
+    #[test]
+    fn test_normalize_drops_repeated_exif() {
+        let mut png = Png::from_chunks(vec![
+            chunk_from_strings("IHDR", "header").unwrap(),
+            chunk_from_strings("eXIf", "MM").unwrap(),
+            chunk_from_strings("eXIf", "II").unwrap(),
+            chunk_from_strings("IEND", "").unwrap(),
+        ]);
+        let report = png.normalize(NormalizeOptions::default());
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(png.chunks().len(), 3);
+    }
+
+    fn damaged_png_bytes() -> Vec<u8> {
+        let mut chunks = testing_chunks();
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let mut bytes: Vec<u8> = Png::from_chunks(chunks).as_bytes();
+        // Corrupt the CRC of miDl, the second chunk
+        let crc_position = 8 + 12 + 20 + 12 + 18 - 1;
+        bytes[crc_position] ^= 0xff;
+        bytes.extend(b"garbage");
+        bytes
+    }
+
+    #[test]
+    fn test_lenient_parse() {
+        let bytes = damaged_png_bytes();
+        assert!(Png::try_from(bytes.as_ref()).is_err());
+
+        let (png, diagnostics) = Png::try_from_lenient(&bytes).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(matches!(
+            diagnostics[0],
+            ParseDiagnostic::CrcMismatch { index: 1, .. }
+        ));
+        assert!(matches!(
+            diagnostics[1],
+            ParseDiagnostic::TrailingData { length: 7, .. }
+        ));
+    }
+
+    #[test]
+    fn test_lenient_parse_truncated() {
+        let mut bytes = damaged_png_bytes();
+        bytes.truncate(8 + 12 + 20 + 10);
+
+        let (png, diagnostics) = Png::try_from_lenient(&bytes).unwrap();
+        assert_eq!(png.chunks().len(), 1);
+        assert_eq!(
+            diagnostics,
+            vec![
+                ParseDiagnostic::TruncatedChunk {
+                    offset: 40,
+                    length: 10
+                },
+                ParseDiagnostic::MissingIend
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repair_crcs() {
+        let mut bytes = damaged_png_bytes();
+        // Also corrupt the CRC of FrSt, which is critical
+        bytes[8 + 12 + 20 - 1] ^= 0xff;
+
+        let (mut png, _) = Png::try_from_lenient(&bytes).unwrap();
+        assert_eq!(png.repair_crcs(true), vec![1]);
+        assert_eq!(png.repair_crcs(false), vec![0]);
+        assert!(Png::try_from(png.as_bytes().as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_trailer() {
+        let mut chunks = testing_chunks();
+        chunks.push(chunk_from_strings("IEND", "").unwrap());
+        let mut bytes = Png::from_chunks(chunks).as_bytes();
+        bytes.extend(b"hidden after IEND");
+
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.chunks().len(), 4);
+        assert_eq!(png.trailer(), b"hidden after IEND");
+        assert_eq!(png.as_bytes(), bytes);
+
+        let old = png.set_trailer(Vec::new());
+        assert_eq!(old, b"hidden after IEND");
+        assert_eq!(png.as_bytes().len(), bytes.len() - old.len());
+    }
+
+    #[test]
+    fn test_lenient_parse_keeps_trailer() {
+        let (png, _) = Png::try_from_lenient(&damaged_png_bytes()).unwrap();
+        assert_eq!(png.trailer(), b"garbage");
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -0,0 +1,360 @@
+use std::fmt::{self, Display};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::known_chunk::{ColorType, ImageHeader, KnownChunk};
+use crate::png::Png;
+use crate::text_chunk::TextChunk;
+
+/// The property bits encoded in the case of each chunk type letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChunkFlags {
+    pub critical: bool,
+    pub public: bool,
+    pub reserved_bit_valid: bool,
+    pub safe_to_copy: bool,
+}
+
+impl From<&ChunkType> for ChunkFlags {
+    fn from(chunk_type: &ChunkType) -> Self {
+        Self {
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            reserved_bit_valid: chunk_type.is_reserved_bit_valid(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+        }
+    }
+}
+
+impl Display for ChunkFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}",
+            if self.critical {
+                "critical"
+            } else {
+                "ancillary"
+            },
+            if self.public { "public" } else { "private" },
+            if self.safe_to_copy {
+                "safe to copy"
+            } else {
+                "unsafe to copy"
+            }
+        )?;
+        if !self.reserved_bit_valid {
+            write!(f, ", reserved bit set")?;
+        }
+        Ok(())
+    }
+}
+
+/// The typed contents of a chunk pngme knows how to decode.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Decoded {
+    Known(KnownChunk),
+    Text(TextChunk),
+}
+
+impl Decoded {
+    /// Decodes `chunk`, returning `None` if pngme doesn't understand its type.
+    pub fn from_chunk(
+        chunk: &Chunk,
+        color_type: Option<ColorType>,
+    ) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if TextChunk::is_text_chunk_type(&chunk.chunk_type().to_string()) {
+            Ok(Some(Decoded::Text(TextChunk::try_from(chunk)?)))
+        } else {
+            Ok(chunk.known(color_type)?.map(Decoded::Known))
+        }
+    }
+}
+
+impl Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Decoded::Known(known) => write!(f, "{}", known),
+            Decoded::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+/// Everything `print` shows about a single chunk.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChunkReport<'a> {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: &'a ChunkType,
+    pub length: u32,
+    pub crc: u32,
+    pub flags: ChunkFlags,
+    pub decoded: Option<Decoded>,
+    pub preview: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Everything `print` shows about a PNG file.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PrintReport<'a> {
+    pub file: &'a str,
+    pub file_size: usize,
+    pub header: Option<ImageHeader>,
+    pub chunk_count: usize,
+    pub idat_size: u64,
+    pub idat_chunks: usize,
+    pub compression_ratio: Option<f64>,
+    pub trailer_size: usize,
+    pub chunks: Vec<ChunkReport<'a>>,
+}
+
+impl<'a> PrintReport<'a> {
+    pub fn new(file: &'a str, file_size: usize, png: &'a Png) -> Self {
+        let header = png.image_header();
+        let color_type = header.as_ref().map(|header| header.color_type);
+
+        let idat_chunks: Vec<&Chunk> = png
+            .chunks()
+            .iter()
+            .filter(|c| c.chunk_type().to_string() == "IDAT")
+            .collect();
+        let idat_size: u64 = idat_chunks.iter().map(|c| c.length() as u64).sum();
+
+        let compression_ratio = match &header {
+            Some(header) if idat_size > 0 => Some(header.raw_data_size() as f64 / idat_size as f64),
+            _ => None,
+        };
+
+        let chunks = png
+            .chunks()
+            .iter()
+            .zip(png.chunk_offsets())
+            .enumerate()
+            .map(|(index, (chunk, offset))| {
+                let (decoded, error) = match Decoded::from_chunk(chunk, color_type) {
+                    Ok(decoded) => (decoded, None),
+                    Err(e) => (None, Some(e.to_string())),
+                };
+                let preview = if decoded.is_none() && !chunk.chunk_data().is_empty() {
+                    Some(preview(chunk.chunk_data()))
+                } else {
+                    None
+                };
+
+                ChunkReport {
+                    index,
+                    offset,
+                    chunk_type: chunk.chunk_type(),
+                    length: chunk.length(),
+                    crc: chunk.crc(),
+                    flags: ChunkFlags::from(chunk.chunk_type()),
+                    decoded,
+                    preview,
+                    error,
+                }
+            })
+            .collect();
+
+        Self {
+            file,
+            file_size,
+            header,
+            chunk_count: png.chunks().len(),
+            idat_size,
+            idat_chunks: idat_chunks.len(),
+            compression_ratio,
+            trailer_size: png.trailer().len(),
+            chunks,
+        }
+    }
+}
+
+impl Display for PrintReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "PNG {}", self.file)?;
+        writeln!(f, "  File size: {} bytes", self.file_size)?;
+        if let Some(header) = &self.header {
+            writeln!(f, "  Dimensions: {}x{}", header.width, header.height)?;
+            writeln!(f, "  Bit depth: {}", header.bit_depth)?;
+            writeln!(f, "  Color type: {}", header.color_type)?;
+        }
+        writeln!(f, "  Chunks: {}", self.chunk_count)?;
+        writeln!(
+            f,
+            "  IDAT: {} bytes in {} chunk(s)",
+            self.idat_size, self.idat_chunks
+        )?;
+        if let Some(ratio) = self.compression_ratio {
+            writeln!(f, "  Compression ratio: {:.2}:1", ratio)?;
+        }
+        if self.trailer_size > 0 {
+            writeln!(f, "  Trailing data after IEND: {} bytes", self.trailer_size)?;
+        }
+
+        for chunk in &self.chunks {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "[{}] {} at offset {:#010x}",
+                chunk.index, chunk.chunk_type, chunk.offset
+            )?;
+            writeln!(f, "  Length: {}", chunk.length)?;
+            writeln!(f, "  CRC: {:#010x}", chunk.crc)?;
+            writeln!(f, "  Flags: {}", chunk.flags)?;
+            if let Some(decoded) = &chunk.decoded {
+                for line in decoded.to_string().lines() {
+                    writeln!(f, "  {}", line)?;
+                }
+            }
+            if let Some(preview) = &chunk.preview {
+                writeln!(f, "  Preview: {}", preview)?;
+            }
+            if let Some(error) = &chunk.error {
+                writeln!(f, "  Warning: Could not decode chunk: {}", error)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The message found by `decode`. Not finding one is an error. Only the message is
+/// shown as text, the rest is for JSON and YAML output.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DecodeReport {
+    pub chunk_type: String,
+    pub keyword: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub label: Option<String>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub index: Option<usize>,
+    pub message: String,
+}
+
+impl Display for DecodeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Message: {}", self.message)
+    }
+}
+
+/// A message found by `decode --all` or `decode --auto`. The index counts chunks
+/// of the same type and the offset is from the start of the file.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FoundMessage {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: String,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Every message found by `decode --all` or `decode --auto`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DecodeAllReport {
+    pub messages: Vec<FoundMessage>,
+}
+
+impl Display for DecodeAllReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Found {} message(s):", self.messages.len())?;
+        for found in &self.messages {
+            write!(
+                f,
+                "\n  [{}] {} at offset {:#010x}",
+                found.index, found.chunk_type, found.offset
+            )?;
+            if let Some(label) = &found.label {
+                write!(f, " labelled '{}'", label)?;
+            }
+            write!(f, ": ")?;
+            match (&found.message, &found.error) {
+                (Some(message), _) => write!(f, "{}", message)?,
+                (None, Some(error)) => write!(f, "Warning: Could not decode message: {}", error)?,
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A pngme payload listed by `list-messages`. The index counts chunks of the same
+/// type and the offset is from the start of the file.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MessageSummary {
+    pub index: usize,
+    pub offset: usize,
+    pub chunk_type: String,
+    pub label: Option<String>,
+    pub size: usize,
+    pub encrypted: bool,
+    pub share: bool,
+    pub error: Option<String>,
+}
+
+/// Every pngme payload found by `list-messages`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MessageListReport {
+    pub messages: Vec<MessageSummary>,
+}
+
+impl Display for MessageListReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.messages.is_empty() {
+            return write!(f, "No messages were found in the file");
+        }
+
+        write!(f, "Found {} message(s):", self.messages.len())?;
+        for message in &self.messages {
+            write!(
+                f,
+                "\n  [{}] {} at offset {:#010x}: ",
+                message.index, message.chunk_type, message.offset
+            )?;
+            match (&message.label, &message.error) {
+                (_, Some(error)) => write!(f, "Warning: Could not read payload: {}", error)?,
+                (Some(label), None) => write!(f, "'{}', {} bytes", label, message.size)?,
+                (None, None) => write!(f, "unlabelled, {} bytes", message.size)?,
+            }
+            if message.encrypted {
+                write!(f, ", encrypted")?;
+            }
+            if message.share {
+                write!(f, ", share of a split message")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Shows the start of `data` as text if it is printable UTF-8, or as hex otherwise
+fn preview(data: &[u8]) -> String {
+    const PREVIEW_LENGTH: usize = 32;
+
+    let truncated = &data[..data.len().min(PREVIEW_LENGTH)];
+    let ellipsis = if data.len() > PREVIEW_LENGTH {
+        "..."
+    } else {
+        ""
+    };
+
+    match std::str::from_utf8(truncated) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => {
+            format!("{:?}{}", text, ellipsis)
+        }
+        _ => {
+            let hex: Vec<String> = truncated.iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{}{}", hex.join(" "), ellipsis)
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use crate::chunk::Chunk;
+use crate::png::Png;
+
+/// A chunk found by `scan_chunks`, with its byte offset in the scanned data.
+pub struct ScannedChunk {
+    pub offset: usize,
+    pub chunk: Chunk,
+}
+
+/// A PNG recovered by `carve`.
+pub struct CarvedPng {
+    /// Byte offset of the PNG signature in the scanned data
+    pub offset: usize,
+    /// Every intact chunk that was found, up to and including `IEND`
+    pub png: Png,
+    /// True if the chunks ran without gaps from the signature to `IEND`
+    pub complete: bool,
+}
+
+/// Returns the offset of every PNG signature in `bytes`, including ones inside
+/// chunk data or after `IEND`.
+pub fn find_signatures(bytes: &[u8]) -> Vec<usize> {
+    let mut signatures = Vec::new();
+    let mut position = 0;
+
+    // Jump between occurrences of the signature's first byte, like memchr
+    while let Some(found) = bytes[position..]
+        .iter()
+        .position(|&byte| byte == Png::STANDARD_HEADER[0])
+    {
+        let offset = position + found;
+        if bytes[offset..].starts_with(&Png::STANDARD_HEADER) {
+            signatures.push(offset);
+        }
+        position = offset + 1;
+    }
+    signatures
+}
+
+/// Reads the chunk starting at `offset` if it has a letter-only type and a valid CRC.
+fn chunk_at(bytes: &[u8], offset: usize) -> Option<Chunk> {
+    let header = bytes.get(offset..offset + 8)?;
+    if !header[4..8].iter().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    let length = u32::from_be_bytes(header[0..4].try_into().ok()?) as usize;
+    let end = offset.checked_add(12)?.checked_add(length)?;
+    Chunk::try_from(bytes.get(offset..end)?).ok()
+}
+
+/// Finds the first intact chunk starting at an offset in `start..end`. `chunk_at`
+/// rejects offsets without a four letter type before looking at the CRC, so only
+/// those few candidates are checksummed.
+fn next_chunk(bytes: &[u8], start: usize, end: usize) -> Option<(usize, Chunk)> {
+    let last = end.min(bytes.len().checked_sub(12)? + 1);
+    (start..last).find_map(|offset| chunk_at(bytes, offset).map(|chunk| (offset, chunk)))
+}
+
+/// Salvages every intact chunk from `bytes` starting at `start`. Whenever the
+/// data at the current position isn't a valid chunk, the scanner resyncs at the
+/// next offset holding a chunk type and a valid CRC.
+pub fn scan_chunks(bytes: &[u8], start: usize) -> Vec<ScannedChunk> {
+    let mut found = Vec::new();
+    let mut position = start;
+
+    while let Some((offset, chunk)) = next_chunk(bytes, position, bytes.len()) {
+        position = offset + 12 + chunk.length() as usize;
+        found.push(ScannedChunk { offset, chunk });
+    }
+
+    found
+}
+
+/// Recovers every PNG embedded in `bytes`, whether the file is several PNGs
+/// concatenated, one PNG hidden inside another's chunk data or a truncated PNG.
+///
+/// Each signature starts a new PNG. Its chunks are read until `IEND` or the end of
+/// the data, resyncing after damaged chunks like `scan_chunks`. A resync never
+/// looks past the next signature, which starts a PNG of its own, so no byte is
+/// scanned for more than one PNG.
+pub fn carve(bytes: &[u8]) -> Vec<CarvedPng> {
+    let signatures = find_signatures(bytes);
+
+    signatures
+        .iter()
+        .map(|&offset| {
+            let mut chunks = Vec::new();
+            let mut complete = true;
+            let mut position = offset + Png::STANDARD_HEADER.len();
+
+            loop {
+                let chunk = match chunk_at(bytes, position) {
+                    Some(chunk) => chunk,
+                    None => {
+                        let next_signature = signatures.partition_point(|&s| s <= position);
+                        let limit = signatures.get(next_signature).copied();
+                        let Some((offset, chunk)) =
+                            next_chunk(bytes, position + 1, limit.unwrap_or(bytes.len()))
+                        else {
+                            break;
+                        };
+                        complete = false;
+                        position = offset;
+                        chunk
+                    }
+                };
+
+                let is_iend = chunk.chunk_type().to_string() == "IEND";
+                position += 12 + chunk.length() as usize;
+                chunks.push(chunk);
+                if is_iend {
+                    break;
+                }
+            }
+
+            let has_iend = chunks
+                .last()
+                .is_some_and(|chunk| chunk.chunk_type().to_string() == "IEND");
+
+            CarvedPng {
+                offset,
+                png: Png::from_chunks(chunks),
+                complete: complete && has_iend,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    fn testing_png_bytes(message: &str) -> Vec<u8> {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("IDAT", message.as_bytes()),
+            chunk("IEND", &[]),
+        ])
+        .as_bytes()
+    }
+
+    #[test]
+    fn test_find_signatures() {
+        let mut bytes = testing_png_bytes("first");
+        let second = bytes.len() + 3;
+        bytes.extend(b"xyz");
+        bytes.extend(testing_png_bytes("second"));
+        assert_eq!(find_signatures(&bytes), vec![0, second]);
+    }
+
+    #[test]
+    fn test_scan_chunks_resyncs() {
+        let mut bytes = testing_png_bytes("first");
+        // Damage the IDAT type so the scanner has to skip it
+        bytes[8 + 25 + 4] = b'1';
+
+        let found = scan_chunks(&bytes, 8);
+        let types: Vec<String> = found
+            .iter()
+            .map(|c| c.chunk.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IEND"]);
+        assert_eq!(found[1].offset, bytes.len() - 12);
+    }
+
+    #[test]
+    fn test_carve_concatenated() {
+        let mut bytes = testing_png_bytes("first");
+        bytes.extend(testing_png_bytes("second"));
+
+        let carved = carve(&bytes);
+        assert_eq!(carved.len(), 2);
+        assert!(carved.iter().all(|c| c.complete));
+        assert_eq!(carved[1].png.as_bytes(), testing_png_bytes("second"));
+    }
+
+    #[test]
+    fn test_carve_embedded_and_truncated() {
+        let inner = testing_png_bytes("inner");
+        let outer = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("thMb", &inner),
+            chunk("IDAT", b"outer"),
+        ])
+        .as_bytes();
+
+        let carved = carve(&outer);
+        assert_eq!(carved.len(), 2);
+        assert!(!carved[0].complete);
+        assert_eq!(carved[0].png.chunks().len(), 3);
+        assert!(carved[1].complete);
+        assert_eq!(carved[1].png.as_bytes(), inner);
+    }
+
+    #[test]
+    fn test_carve_resyncs_after_damage() {
+        let mut bytes = Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("tEXt", b"Comment\0damaged"),
+            chunk("IDAT", b"first"),
+            chunk("IEND", &[]),
+        ])
+        .as_bytes();
+        // Damage the tEXt CRC, the chunks after it are still recovered
+        let text_crc = 8 + 25 + 8 + 15;
+        bytes[text_crc] ^= 0xff;
+        bytes.extend(vec![0x89; 1000]);
+        bytes.extend(testing_png_bytes("second"));
+
+        let carved = carve(&bytes);
+        assert_eq!(carved.len(), 2);
+        assert!(!carved[0].complete);
+        let types: Vec<String> = carved[0]
+            .png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(carved[0].png.chunks()[1].chunk_data(), b"first");
+        assert!(carved[1].complete);
+        assert_eq!(carved[1].png.as_bytes(), testing_png_bytes("second"));
+    }
+}
@@ -0,0 +1,233 @@
+use std::collections::HashSet;
+
+use crate::crypto::random_fill;
+
+/// Length of the random id shared by every share of one split.
+pub const SET_ID_LENGTH: usize = 8;
+
+/// One share of a secret split with `split`.
+///
+/// ```text
+/// set id (8 bytes) | threshold (1 byte) | x (1 byte) | y values
+/// ```
+///
+/// The set id tells shares of different secrets apart, `x` is the share's point
+/// on the polynomials and there is one `y` value per byte of the secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub set_id: [u8; SET_ID_LENGTH],
+    pub threshold: u8,
+    pub x: u8,
+    pub y: Vec<u8>,
+}
+
+impl Share {
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.set_id.to_vec();
+        bytes.push(self.threshold);
+        bytes.push(self.x);
+        bytes.extend(&self.y);
+        bytes
+    }
+}
+
+impl TryFrom<&[u8]> for Share {
+    type Error = crate::Error;
+
+    fn try_from(bytes: &[u8]) -> crate::Result<Self> {
+        if bytes.len() < SET_ID_LENGTH + 2 {
+            return Err("Share is truncated".into());
+        }
+        let (set_id, rest) = bytes.split_at(SET_ID_LENGTH);
+        if rest[0] == 0 || rest[1] == 0 {
+            return Err("Share has an invalid threshold or x coordinate".into());
+        }
+        Ok(Self {
+            set_id: set_id.try_into()?,
+            threshold: rest[0],
+            x: rest[1],
+            y: rest[2..].to_vec(),
+        })
+    }
+}
+
+/// Splits `secret` into `shares` shares so that any `threshold` of them recover it
+/// and fewer reveal nothing about it.
+///
+/// Each byte of the secret is the constant term of a random polynomial of degree
+/// `threshold - 1` over GF(256), and share `x` holds every polynomial's value at `x`.
+pub fn split(secret: &[u8], shares: u8, threshold: u8) -> crate::Result<Vec<Share>> {
+    if threshold == 0 || threshold > shares {
+        return Err(format!(
+            "The threshold must be between 1 and the number of shares ({}), not {}",
+            shares, threshold
+        )
+        .into());
+    }
+
+    let mut set_id = [0; SET_ID_LENGTH];
+    random_fill(&mut set_id)?;
+
+    // coefficients[i] holds the non-constant coefficients of byte i's polynomial
+    let mut coefficients = vec![0; secret.len() * (threshold as usize - 1)];
+    random_fill(&mut coefficients)?;
+    let coefficients: Vec<&[u8]> = match threshold {
+        1 => vec![&[][..]; secret.len()],
+        _ => coefficients.chunks(threshold as usize - 1).collect(),
+    };
+
+    Ok((1..=shares)
+        .map(|x| Share {
+            set_id,
+            threshold,
+            x,
+            y: secret
+                .iter()
+                .zip(&coefficients)
+                .map(|(&constant, coefficients)| evaluate(constant, coefficients, x))
+                .collect(),
+        })
+        .collect())
+}
+
+/// Recovers the secret from at least `threshold` shares of the same split.
+pub fn combine(shares: &[Share]) -> crate::Result<Vec<u8>> {
+    let first = shares.first().ok_or("No shares were given")?;
+
+    if shares
+        .iter()
+        .any(|share| share.set_id != first.set_id || share.threshold != first.threshold)
+    {
+        return Err("The shares belong to different secrets".into());
+    }
+    if shares.iter().any(|share| share.y.len() != first.y.len()) {
+        return Err("The shares have different lengths".into());
+    }
+
+    let mut seen = HashSet::new();
+    let shares: Vec<&Share> = shares
+        .iter()
+        .filter(|share| seen.insert(share.x))
+        .take(first.threshold as usize)
+        .collect();
+    if shares.len() < first.threshold as usize {
+        return Err(format!(
+            "{} different share(s) were found but {} are needed",
+            shares.len(),
+            first.threshold
+        )
+        .into());
+    }
+
+    // Lagrange interpolation at x = 0. In GF(256) subtraction is XOR, so the basis
+    // polynomial for share j at 0 is the product of x_m / (x_m ^ x_j) for m != j.
+    let basis: Vec<u8> = shares
+        .iter()
+        .map(|j| {
+            shares
+                .iter()
+                .filter(|m| m.x != j.x)
+                .fold(1, |product, m| mul(product, div(m.x, m.x ^ j.x)))
+        })
+        .collect();
+
+    Ok((0..first.y.len())
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&basis)
+                .fold(0, |sum, (share, &basis)| sum ^ mul(share.y[i], basis))
+        })
+        .collect())
+}
+
+/// Evaluates the polynomial with the given constant term and higher coefficients at `x`.
+fn evaluate(constant: u8, coefficients: &[u8], x: u8) -> u8 {
+    // Horner's method, starting from the highest coefficient
+    let higher = coefficients
+        .iter()
+        .rev()
+        .fold(0, |result, &coefficient| mul(result, x) ^ coefficient);
+    mul(higher, x) ^ constant
+}
+
+/// Multiplies in GF(256) with the AES polynomial x^8 + x^4 + x^3 + x + 1.
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// Divides in GF(256). Every non-zero element has an inverse, a^254.
+fn div(a: u8, b: u8) -> u8 {
+    debug_assert!(b != 0, "division by zero in GF(256)");
+    let mut inverse = 1;
+    for _ in 0..254 {
+        inverse = mul(inverse, b);
+    }
+    mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_arithmetic() {
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(mul(0x57, 0x13), 0xfe);
+        for a in 1..=255 {
+            assert_eq!(mul(a, div(1, a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_split_and_combine() {
+        let secret = b"attack at dawn";
+        let shares = split(secret, 5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        assert_eq!(combine(&shares[..3]).unwrap(), secret);
+        assert_eq!(combine(&shares[2..]).unwrap(), secret);
+        let some = [shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(combine(&some).unwrap(), secret);
+        assert!(combine(&shares[..2]).is_err());
+        assert!(combine(&[shares[0].clone(), shares[0].clone(), shares[0].clone()]).is_err());
+    }
+
+    #[test]
+    fn test_threshold_of_one() {
+        let shares = split(b"hi", 2, 1).unwrap();
+        assert_eq!(shares[1].y, b"hi");
+        assert_eq!(combine(&shares[1..]).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn test_invalid_splits() {
+        assert!(split(b"hi", 2, 3).is_err());
+        assert!(split(b"hi", 2, 0).is_err());
+
+        let a = split(b"hi", 3, 2).unwrap();
+        let b = split(b"hi", 3, 2).unwrap();
+        assert!(combine(&[a[0].clone(), b[1].clone()]).is_err());
+    }
+
+    #[test]
+    fn test_share_bytes() {
+        let share = split(b"hi", 3, 2).unwrap().remove(1);
+        let bytes = share.as_bytes();
+        assert_eq!(bytes.len(), SET_ID_LENGTH + 4);
+        assert_eq!(Share::try_from(bytes.as_slice()).unwrap(), share);
+        assert!(Share::try_from(&bytes[..5]).is_err());
+    }
+}
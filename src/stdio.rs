@@ -0,0 +1,94 @@
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use crate::atomic::{self, WriteOptions};
+
+/// The path that stands for stdin when reading and stdout when writing.
+pub const STDIO_PATH: &str = "-";
+
+/// Returns true if `path` is `-`.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+/// Reads the whole of `path`, or of stdin if `path` is `-`.
+pub fn read_input(path: impl AsRef<Path>) -> crate::Result<Vec<u8>> {
+    let path = path.as_ref();
+    if is_stdio(path) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+/// Reads a password from the first line of `file` if one is given, otherwise from the
+/// `env` environment variable, and otherwise asks for it on the terminal without
+/// echoing it. Passwords never go on the command line, where other users can see them.
+pub fn read_password(prompt: &str, env: &str, file: Option<&str>) -> crate::Result<String> {
+    let password = match (file, std::env::var(env)) {
+        (Some(file), _) => {
+            let contents = fs::read_to_string(file)?;
+            contents.lines().next().unwrap_or_default().to_string()
+        }
+        (None, Ok(password)) => password,
+        (None, Err(_)) => rpassword::prompt_password(prompt)?,
+    };
+    if password.is_empty() {
+        return Err("The password is empty".into());
+    }
+    Ok(password)
+}
+
+/// Writes `bytes` to `path`, or to stdout if `path` is `-`. Files are written
+/// with `atomic::write_file`.
+pub fn write_output(
+    path: impl AsRef<Path>,
+    bytes: &[u8],
+    in_place: bool,
+    options: &WriteOptions,
+) -> crate::Result<()> {
+    let path = path.as_ref();
+    if is_stdio(path) {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+    } else {
+        atomic::write_file(path, bytes, in_place, options)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_stdio() {
+        assert!(is_stdio(Path::new("-")));
+        assert!(!is_stdio(Path::new("./-")));
+        assert!(!is_stdio(Path::new("image.png")));
+    }
+
+    #[test]
+    fn test_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("pngme-stdio-{}", std::process::id()));
+        write_output(&path, b"bytes", false, &WriteOptions::default()).unwrap();
+        assert_eq!(read_input(&path).unwrap(), b"bytes");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_read_password_from_file() {
+        let path = std::env::temp_dir().join(format!("pngme-password-{}", std::process::id()));
+        fs::write(&path, "secret\nignored\n").unwrap();
+        let file = path.to_str();
+        assert_eq!(read_password("", "PNGME_TEST_UNSET", file).unwrap(), "secret");
+
+        fs::write(&path, "\n").unwrap();
+        assert!(read_password("", "PNGME_TEST_UNSET", file).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,365 @@
+use std::fmt::{self, Display};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+
+/// A decoded `tEXt`, `zTXt` or `iTXt` chunk.
+#[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type"))]
+pub enum TextChunk {
+    /// `tEXt`: uncompressed Latin-1 text
+    Text { keyword: String, text: String },
+    /// `zTXt`: zlib compressed Latin-1 text
+    Compressed { keyword: String, text: String },
+    /// `iTXt`: UTF-8 text with a language tag, optionally compressed
+    International {
+        keyword: String,
+        compressed: bool,
+        language_tag: String,
+        translated_keyword: String,
+        text: String,
+    },
+}
+
+impl TextChunk {
+    /// Creates an uncompressed text entry stored under `chunk_type`, which must be
+    /// one of `tEXt`, `zTXt` or `iTXt`.
+    pub fn new(
+        chunk_type: &str,
+        keyword: String,
+        text: String,
+    ) -> Result<TextChunk, Box<dyn std::error::Error>> {
+        check_keyword(&keyword)?;
+        match chunk_type {
+            "tEXt" => Ok(TextChunk::Text { keyword, text }),
+            "zTXt" => Ok(TextChunk::Compressed { keyword, text }),
+            "iTXt" => Ok(TextChunk::International {
+                keyword,
+                compressed: false,
+                language_tag: String::new(),
+                translated_keyword: String::new(),
+                text,
+            }),
+            _ => Err(format!("Chunk of type '{}' is not a text chunk", chunk_type).into()),
+        }
+    }
+
+    pub fn keyword(&self) -> &str {
+        match self {
+            TextChunk::Text { keyword, .. }
+            | TextChunk::Compressed { keyword, .. }
+            | TextChunk::International { keyword, .. } => keyword,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TextChunk::Text { text, .. }
+            | TextChunk::Compressed { text, .. }
+            | TextChunk::International { text, .. } => text,
+        }
+    }
+
+    /// Returns the chunk type this text is stored under.
+    pub fn chunk_type(&self) -> &'static str {
+        match self {
+            TextChunk::Text { .. } => "tEXt",
+            TextChunk::Compressed { .. } => "zTXt",
+            TextChunk::International { .. } => "iTXt",
+        }
+    }
+
+    /// Returns true if `chunk_type` is one of the three textual chunk types.
+    pub fn is_text_chunk_type(chunk_type: &str) -> bool {
+        matches!(chunk_type, "tEXt" | "zTXt" | "iTXt")
+    }
+
+    /// Serializes this text into a new `Chunk`, compressing it where required.
+    pub fn to_chunk(&self) -> Result<Chunk, Box<dyn std::error::Error>> {
+        check_keyword(self.keyword())?;
+
+        let mut data: Vec<u8> = Vec::new();
+        match self {
+            TextChunk::Text { keyword, text } => {
+                data.extend(to_latin1(keyword)?);
+                data.push(0);
+                data.extend(to_latin1(text)?);
+            }
+            TextChunk::Compressed { keyword, text } => {
+                data.extend(to_latin1(keyword)?);
+                data.push(0);
+                data.push(0); // compression method: zlib
+                data.extend(deflate(&to_latin1(text)?)?);
+            }
+            TextChunk::International {
+                keyword,
+                compressed,
+                language_tag,
+                translated_keyword,
+                text,
+            } => {
+                data.extend(to_latin1(keyword)?);
+                data.push(0);
+                data.push(*compressed as u8);
+                data.push(0); // compression method: zlib
+                data.extend(language_tag.as_bytes());
+                data.push(0);
+                data.extend(translated_keyword.as_bytes());
+                data.push(0);
+                if *compressed {
+                    data.extend(deflate(text.as_bytes())?);
+                } else {
+                    data.extend(text.as_bytes());
+                }
+            }
+        }
+
+        Ok(Chunk::new(ChunkType::from_str(self.chunk_type())?, data))
+    }
+}
+
+impl TryFrom<&Chunk> for TextChunk {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(chunk: &Chunk) -> Result<Self, Self::Error> {
+        let chunk_type = chunk.chunk_type().to_string();
+        let data = chunk.chunk_data();
+
+        let (keyword, rest) = split_at_null(data).ok_or("Text chunk is missing a keyword")?;
+        let keyword = from_latin1(keyword);
+        check_keyword(&keyword)?;
+
+        match chunk_type.as_str() {
+            "tEXt" => Ok(TextChunk::Text {
+                keyword,
+                text: from_latin1(rest),
+            }),
+            "zTXt" => {
+                let (&method, compressed) = rest
+                    .split_first()
+                    .ok_or("zTXt chunk is missing its compression method")?;
+                check_compression_method(method)?;
+                Ok(TextChunk::Compressed {
+                    keyword,
+                    text: from_latin1(&inflate(compressed, MAX_INFLATED_SIZE)?),
+                })
+            }
+            "iTXt" => {
+                if rest.len() < 2 {
+                    return Err("iTXt chunk is missing its compression fields".into());
+                }
+                let compressed = match rest[0] {
+                    0 => false,
+                    1 => true,
+                    flag => return Err(format!("Invalid iTXt compression flag {}", flag).into()),
+                };
+                check_compression_method(rest[1])?;
+
+                let (language_tag, rest) =
+                    split_at_null(&rest[2..]).ok_or("iTXt chunk is missing its language tag")?;
+                let (translated_keyword, text) =
+                    split_at_null(rest).ok_or("iTXt chunk is missing its translated keyword")?;
+
+                let text = if compressed {
+                    inflate(text, MAX_INFLATED_SIZE)?
+                } else {
+                    text.to_vec()
+                };
+
+                Ok(TextChunk::International {
+                    keyword,
+                    compressed,
+                    language_tag: String::from_utf8(language_tag.to_vec())?,
+                    translated_keyword: String::from_utf8(translated_keyword.to_vec())?,
+                    text: String::from_utf8(text)?,
+                })
+            }
+            _ => Err(format!("Chunk of type '{}' is not a text chunk", chunk_type).into()),
+        }
+    }
+}
+
+impl Display for TextChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} {{", self.chunk_type())?;
+        writeln!(f, "  Keyword: {}", self.keyword())?;
+        if let TextChunk::International {
+            compressed,
+            language_tag,
+            translated_keyword,
+            ..
+        } = self
+        {
+            writeln!(f, "  Compressed: {}", compressed)?;
+            writeln!(f, "  Language: {}", language_tag)?;
+            writeln!(f, "  Translated keyword: {}", translated_keyword)?;
+        }
+        writeln!(f, "  Text: {}", self.text())?;
+        write!(f, "}}")
+    }
+}
+
+/// Keywords must be 1-79 bytes of Latin-1 without a null separator.
+pub fn check_keyword(keyword: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let length = keyword.chars().count();
+    if length == 0 || length > 79 {
+        return Err("Text chunk keyword must be between 1 and 79 characters".into());
+    }
+    if keyword.contains('\0') {
+        return Err("Text chunk keyword can't contain a null character".into());
+    }
+    Ok(())
+}
+
+pub fn check_compression_method(method: u8) -> Result<(), Box<dyn std::error::Error>> {
+    if method != 0 {
+        return Err(format!("Unknown text compression method {}", method).into());
+    }
+    Ok(())
+}
+
+pub fn split_at_null(bytes: &[u8]) -> Option<(&[u8], &[u8])> {
+    let position = bytes.iter().position(|&b| b == 0)?;
+    Some((&bytes[..position], &bytes[position + 1..]))
+}
+
+pub fn from_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+pub fn to_latin1(s: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    s.chars()
+        .map(|c| u8::try_from(c).map_err(|_| format!("'{}' is not a Latin-1 character", c).into()))
+        .collect()
+}
+
+pub fn deflate(bytes: &[u8]) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    Ok(encoder.finish()?)
+}
+
+/// The most a compressed text or ICC profile chunk may inflate to.
+pub const MAX_INFLATED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Inflates zlib compressed `bytes`, failing if the result would be longer than
+/// `limit` bytes so a small chunk can't expand into gigabytes of memory.
+pub fn inflate(bytes: &[u8], limit: u64) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut decoded = Vec::new();
+    ZlibDecoder::new(bytes)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut decoded)?;
+    if decoded.len() as u64 > limit {
+        return Err(format!("Compressed data inflates to more than {} bytes", limit).into());
+    }
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_from_data(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
+
+    #[test]
+    fn test_parse_text() {
+        let chunk = chunk_from_data("tEXt", b"Comment\0Hello \xe9t\xe9");
+        let text = TextChunk::try_from(&chunk).unwrap();
+        assert_eq!(text.keyword(), "Comment");
+        assert_eq!(text.text(), "Hello été");
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        let text = TextChunk::Text {
+            keyword: String::from("Software"),
+            text: String::from("pngme"),
+        };
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(chunk.chunk_data(), b"Software\0pngme");
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let text = TextChunk::Compressed {
+            keyword: String::from("Comment"),
+            text: "a".repeat(200),
+        };
+        let chunk = text.to_chunk().unwrap();
+        assert_eq!(&chunk.chunk_type().to_string(), "zTXt");
+        assert!(chunk.chunk_data().len() < 200);
+        assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+    }
+
+    #[test]
+    fn test_international_round_trip() {
+        for compressed in [false, true] {
+            let text = TextChunk::International {
+                keyword: String::from("Title"),
+                compressed,
+                language_tag: String::from("ja"),
+                translated_keyword: String::from("タイトル"),
+                text: String::from("こんにちは"),
+            };
+            let chunk = text.to_chunk().unwrap();
+            assert_eq!(&chunk.chunk_type().to_string(), "iTXt");
+            assert_eq!(TextChunk::try_from(&chunk).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn test_invalid_keyword() {
+        let text = TextChunk::Text {
+            keyword: String::new(),
+            text: String::from("No keyword"),
+        };
+        assert!(text.to_chunk().is_err());
+
+        let chunk = chunk_from_data("tEXt", b"No separator");
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_non_latin1_text() {
+        let text = TextChunk::Text {
+            keyword: String::from("Comment"),
+            text: String::from("こんにちは"),
+        };
+        assert!(text.to_chunk().is_err());
+    }
+
+    #[test]
+    fn test_new() {
+        let text = TextChunk::new("iTXt", String::from("Comment"), String::from("hi")).unwrap();
+        assert_eq!(text.chunk_type(), "iTXt");
+        assert_eq!(text.keyword(), "Comment");
+        assert!(TextChunk::new("RuSt", String::from("Comment"), String::from("hi")).is_err());
+    }
+
+    #[test]
+    fn test_not_a_text_chunk() {
+        let chunk = chunk_from_data("RuSt", b"Comment\0Hello");
+        assert!(TextChunk::try_from(&chunk).is_err());
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let compressed = deflate(&[0; 1000]).unwrap();
+        assert_eq!(inflate(&compressed, 1000).unwrap().len(), 1000);
+        assert!(inflate(&compressed, 999).is_err());
+
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(deflate(&vec![b'a'; MAX_INFLATED_SIZE as usize + 1]).unwrap());
+        assert!(TextChunk::try_from(&chunk_from_data("zTXt", &data)).is_err());
+    }
+}
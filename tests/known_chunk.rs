@@ -0,0 +1,27 @@
+use pngme::known_chunk::{ColorType, ImageHeader, KnownChunk};
+
+#[test]
+fn test_known_chunks_round_trip_through_the_public_api() {
+    let header = ImageHeader {
+        width: 2,
+        height: 3,
+        bit_depth: 8,
+        color_type: ColorType::Rgba,
+        compression_method: 0,
+        filter_method: 0,
+        interlace_method: 0,
+    };
+    let chunks = [
+        KnownChunk::Header(header),
+        KnownChunk::PhysicalDimensions {
+            x: 2835,
+            y: 2835,
+            unit: 1,
+        },
+    ];
+    for known in chunks {
+        let chunk = known.to_chunk().unwrap();
+        let parsed = KnownChunk::from_chunk(&chunk, Some(ColorType::Rgba)).unwrap();
+        assert_eq!(parsed, Some(known));
+    }
+}